@@ -0,0 +1,24 @@
+use std::time::Duration;
+
+/// Common interface of the consumers turning a sample stream into bands.
+///
+/// Object safe, so the analyzer can be chosen at runtime as a `Box<dyn Analyzer>`.
+/// [`Analyzer::bands`], [`Analyzer::smoothed`] and [`Analyzer::band_frequencies`]
+/// have the same length.
+pub trait Analyzer {
+    /// Reads the available samples, computes any completed frame and smooths the bands
+    /// over `elapsed`, the time since the previous update, even without a new frame.
+    fn update(&mut self, elapsed: Duration);
+
+    /// Whether the last [`Analyzer::update`] computed at least one new frame.
+    fn frame_ready(&self) -> bool;
+
+    /// Band values of the last frame.
+    fn bands(&self) -> &[f32];
+
+    /// Band values smoothed over time.
+    fn smoothed(&self) -> &[f32];
+
+    /// Centre frequency of each band in Hz.
+    fn band_frequencies(&self) -> &[f32];
+}
@@ -1,6 +1,17 @@
-use std::{sync::Arc, time::Duration};
+use std::time::Duration;
 
-use ringbuf::{storage::Heap, traits::Consumer, wrap::caching::Caching, SharedRb};
+use fft_analizer::{
+    chord::{Chord, ChordRecognizer},
+    chroma::Chromagram,
+    key::Key,
+};
+use ringbuf::traits::Consumer;
+
+use crate::{
+    runtime::{FilterBankConfig, RuntimeFilterBankConsumer},
+    smoothing::Smoother,
+    Analyzer, HeapConsumerF32,
+};
 
 pub struct Bandpass {
     b0: f32,
@@ -13,7 +24,7 @@ pub struct Bandpass {
 }
 
 impl Bandpass {
-    fn new(f0: f32, q: f32, fs: f32) -> Self {
+    pub(crate) fn new(f0: f32, q: f32, fs: f32) -> Self {
         let w0 = 2.0 * std::f32::consts::PI * f0 / fs;
         let alpha = w0.sin() / (2.0 * q);
 
@@ -35,7 +46,7 @@ impl Bandpass {
         }
     }
 
-    fn process(&mut self, x: f32) -> f32 {
+    pub(crate) fn process(&mut self, x: f32) -> f32 {
         let y = self.b0 * x + self.z1;
         self.z1 = self.b1 * x + self.z2 - self.a1 * y;
         self.z2 = self.b2 * x - self.a2 * y;
@@ -43,90 +54,140 @@ impl Bandpass {
     }
 }
 
+/// Bank of semitone spaced bandpass filters with sizes fixed at compile time.
+///
+/// Wraps a [`RuntimeFilterBankConsumer`] and copies its results into arrays when an update
+/// computes a frame.
 pub struct FilterBankConsumer<
     const IB_LEN: usize,
     const FB_LEN: usize,
     const DELTA: usize,
     T: Consumer<Item = f32>,
 > {
-    consumer: T,
+    inner: RuntimeFilterBankConsumer<T>,
+    /// Samples of the last frame
     pub samples: [f32; IB_LEN],
     pub frequencies: [f32; FB_LEN],
     pub smoothed: [f32; FB_LEN],
-    pub compressed: [f32; 12],
-    index: usize,
-    filters: Vec<Bandpass>,
+    /// Band energy folded into pitch classes starting at C, see [`Chromagram`]
+    pub chroma: [f32; 12],
+    /// Key of the chroma of the last frames
+    pub key: Option<Key>,
+    /// Chord of the last frame, `None` when no chord matches
+    pub chord: Option<Chord>,
 }
 
 pub type AudioConsumerFilterBankF32<const IB_LEN: usize, const FB_LEN: usize, const DELTA: usize> =
-    FilterBankConsumer<IB_LEN, FB_LEN, DELTA, Caching<Arc<SharedRb<Heap<f32>>>, false, true>>;
+    FilterBankConsumer<IB_LEN, FB_LEN, DELTA, HeapConsumerF32>;
 
 impl<const IB_LEN: usize, const FB_LEN: usize, const DELTA: usize, T: Consumer<Item = f32>>
     FilterBankConsumer<IB_LEN, FB_LEN, DELTA, T>
 {
+    /// # Panics
+    ///
+    /// If `DELTA` is 0 or the settings are rejected by [`FilterBankConfig::validate`].
     pub fn new(consumer: T, sample_rate: f32, f_min: f32, f_max: f32) -> Self {
-        // log-spaced frequencies
-        let mut filters = Vec::new();
-        let q = 200.0; // quality factor (adjust for bandwidth)
-        let mut f = f_min;
-
-        while f <= f_max && filters.len() < FB_LEN {
-            filters.push(Bandpass::new(f, q, sample_rate));
-            f *= 2f32.powf(1.0 / 12.0); // semitone steps
-        }
-
+        let inner = FilterBankConfig::new(IB_LEN, FB_LEN, f_min, f_max)
+            .with_sample_rate(sample_rate)
+            .with_smoothing(1.0 / DELTA as f32, 1.0 / DELTA as f32)
+            .build(consumer)
+            .unwrap_or_else(|error| panic!("{}", error));
         FilterBankConsumer {
-            consumer,
+            inner,
             samples: [0.0; IB_LEN],
             frequencies: [0.0; FB_LEN],
             smoothed: [0.0; FB_LEN],
-            compressed: [0.0; 12],
-            index: 0,
-            filters,
+            chroma: [0.0; 12],
+            key: None,
+            chord: None,
         }
     }
 
-    fn read_samples(&mut self) {
-        while let Some(sample) = self.consumer.try_pop() {
-            self.samples[self.index] = sample;
-            self.index += 1;
-            if self.index == IB_LEN {
-                break;
-            }
-        }
+    /// Detects the key over the last `frames` frames, 32 by default.
+    ///
+    /// # Panics
+    ///
+    /// If `frames` is 0.
+    pub fn with_key_window(mut self, frames: usize) -> Self {
+        self.inner = self.inner.with_key_window(frames);
+        self
     }
 
-    fn process_samples(&mut self, milis: Duration) {
-        if self.index < IB_LEN - 1 {
-            return;
-        }
+    /// Replaces the chord recognizer, smoothed with a self transition probability of 0.9 by default.
+    pub fn with_chord_recognizer(mut self, recognizer: ChordRecognizer) -> Self {
+        self.inner = self.inner.with_chord_recognizer(recognizer);
+        self
+    }
 
-        for i in 0..12 {
-            self.compressed[i] = 0.0;
-        }
+    /// Replaces the chromagram, e.g. to change the reference or the normalization.
+    ///
+    /// The default chromagram keeps the tuning at 0 cents: with Q = 200 filters a semitone
+    /// apart, a peak between two bands barely reaches either of them, so the tuning
+    /// estimated from the band energies is unreliable.
+    pub fn with_chromagram(mut self, chromagram: Chromagram) -> Self {
+        self.inner = self.inner.with_chromagram(chromagram);
+        self
+    }
 
-        for (i, filter) in self.filters.iter_mut().enumerate() {
-            let mut energy = 0.0;
-            for &s in self.samples.iter() {
-                let y = filter.process(s);
-                energy += y * y;
-            }
-            self.frequencies[i] = (energy / IB_LEN as f32).sqrt(); // RMS energy
-            let note_index = i % 12;
-            self.compressed[note_index] += self.frequencies[i];
+    /// Replaces the smoothing of `smoothed`, a time constant of `1 / DELTA` seconds by default.
+    pub fn with_smoother(mut self, smoother: Smoother) -> Self {
+        let config = self
+            .inner
+            .config()
+            .clone()
+            .with_smoothing(smoother.attack(), smoother.release())
+            .with_smoothing_mode(smoother.mode());
+        if let Err(error) = self.inner.reconfigure(config) {
+            panic!("{}", error)
         }
+        self
+    }
 
-        let m = (milis.as_nanos() / 1_000_000) as f64;
-        for i in 0..self.frequencies.len() {
-            self.smoothed[i] +=
-                (self.frequencies[i] - self.smoothed[i]) * (m / 1000.0) as f32 * DELTA as f32;
-        }
+    /// Centre frequency of each band in Hz.
+    pub fn band_frequencies(&self) -> &[f32] {
+        self.inner.band_frequencies()
+    }
 
-        self.index = 0;
+    /// Tuning of the chromagram in cents, 0 unless one estimating it was set with `with_chromagram`.
+    pub fn tuning(&self) -> f32 {
+        self.inner.tuning()
     }
 
     pub fn update(&mut self, milis: Duration) {
-        self.read_samples();
-        self.process_samples(milis);
+        self.inner.update(milis);
+        let bands = self.inner.bands().len();
+        if self.inner.frame_ready() {
+            self.samples.copy_from_slice(self.inner.samples());
+            self.frequencies[..bands].copy_from_slice(self.inner.bands());
+            self.chroma = *self.inner.chroma();
+            self.key = self.inner.key();
+            self.chord = self.inner.chord();
+        }
+        self.smoothed[..bands].copy_from_slice(self.inner.smoothed());
+    }
+}
+
+impl<const IB_LEN: usize, const FB_LEN: usize, const DELTA: usize, T: Consumer<Item = f32>> Analyzer
+    for FilterBankConsumer<IB_LEN, FB_LEN, DELTA, T>
+{
+    fn update(&mut self, elapsed: Duration) {
+        FilterBankConsumer::update(self, elapsed);
+    }
+
+    fn frame_ready(&self) -> bool {
+        self.inner.frame_ready()
+    }
+
+    /// RMS level of each band, bands above `f_max` are left out.
+    fn bands(&self) -> &[f32] {
+        &self.frequencies[..self.inner.bands().len()]
+    }
+
+    fn smoothed(&self) -> &[f32] {
+        &self.smoothed[..self.inner.bands().len()]
+    }
+
+    fn band_frequencies(&self) -> &[f32] {
+        self.inner.band_frequencies()
     }
 }
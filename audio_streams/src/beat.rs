@@ -0,0 +1,442 @@
+use std::{collections::VecDeque, time::Duration};
+
+/// Fraction of the phase error corrected on each frame.
+const PHASE_GAIN: f32 = 0.2;
+/// Fraction of the best autocorrelation score a peak at half its lag needs to be preferred.
+const OCTAVE_RATIO: f32 = 0.7;
+/// Seconds between tempo estimates, the phase is still followed on every frame.
+const ESTIMATE_INTERVAL: f32 = 0.25;
+
+/// A beat tick.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Beat {
+    /// Time of the frame the beat falls on
+    pub time: Duration,
+    /// Tempo estimate when the beat was emitted
+    pub bpm: f32,
+}
+
+/// Tempo estimation and beat tracking over an onset envelope, such as
+/// [`crate::onset::OnsetDetector::value`].
+///
+/// The tempo is the lag maximizing the autocorrelation of the last seconds of the envelope,
+/// reinforced by its double so the beat is preferred over half-time. The beat phase follows
+/// the comb of that period that best lines up with the envelope.
+#[derive(Debug, Clone)]
+pub struct BeatTracker {
+    /// Envelope frames per second
+    frame_rate: f32,
+    min_bpm: f32,
+    max_bpm: f32,
+    window: usize,
+    envelope: VecDeque<f32>,
+    /// Mean removed copy of the envelope
+    centered: Vec<f32>,
+    scores: Vec<f32>,
+    bpm: f32,
+    /// Beat period in frames from the last estimate, `None` without a tempo
+    period: Option<f32>,
+    /// Frames since the period was last estimated
+    since_estimate: usize,
+    /// Fraction of the beat period elapsed since the last beat
+    phase: f32,
+    locked: bool,
+    /// Frames since the last tick
+    since_tick: usize,
+}
+
+impl BeatTracker {
+    /// Creates a tracker for 60 to 180 BPM over the last 8 seconds of the envelope.
+    ///
+    /// `frame_rate` is the number of envelope values per second, the sample rate
+    /// divided by the hop.
+    ///
+    /// # Panics
+    ///
+    /// If `frame_rate` is not positive, too low for a beat at 180 BPM to last a frame or
+    /// too low for 8 seconds to hold two beats at 60 BPM.
+    pub fn new(frame_rate: f32) -> Self {
+        if frame_rate.is_nan() || frame_rate <= 0.0 {
+            panic!("frame rate ({}) must be positive", frame_rate)
+        }
+        let tracker = BeatTracker {
+            frame_rate,
+            min_bpm: 60.0,
+            max_bpm: 180.0,
+            window: (8.0 * frame_rate) as usize,
+            envelope: VecDeque::new(),
+            centered: Vec::new(),
+            scores: Vec::new(),
+            bpm: 0.0,
+            period: None,
+            since_estimate: 0,
+            phase: 0.0,
+            locked: false,
+            since_tick: 0,
+        };
+        tracker.check_window();
+        tracker
+    }
+
+    /// Limits the estimated tempo to `min_bpm..=max_bpm`.
+    ///
+    /// # Panics
+    ///
+    /// If `min_bpm` is not positive or is greater than `max_bpm`, if a beat at `max_bpm`
+    /// is shorter than a frame, or if the window can't hold two beats at `min_bpm`,
+    /// see [`BeatTracker::with_window`].
+    pub fn with_tempo_range(mut self, min_bpm: f32, max_bpm: f32) -> Self {
+        if min_bpm <= 0.0 || min_bpm > max_bpm {
+            panic!("invalid tempo range {}..={}", min_bpm, max_bpm)
+        }
+        self.min_bpm = min_bpm;
+        self.max_bpm = max_bpm;
+        self.check_window();
+        self
+    }
+
+    /// Length of envelope history the tempo is estimated from.
+    ///
+    /// # Panics
+    ///
+    /// If `window` can't hold two beats at the slowest tempo, the tempo would never be estimated.
+    pub fn with_window(mut self, window: Duration) -> Self {
+        self.window = (window.as_secs_f32() * self.frame_rate) as usize;
+        self.check_window();
+        self
+    }
+
+    pub fn frame_rate(&self) -> f32 {
+        self.frame_rate
+    }
+
+    /// Estimated tempo, 0 until enough envelope has been seen.
+    pub fn bpm(&self) -> f32 {
+        self.bpm
+    }
+
+    /// Position within the current beat, 0 on the beat and rising to 1 just before the next one.
+    pub fn beat_phase(&self) -> f32 {
+        self.phase
+    }
+
+    /// Adds the envelope value of the frame at `time` and returns a beat if one falls on it.
+    ///
+    /// The tempo is re-estimated every 250 ms of envelope rather than on every frame.
+    pub fn push(&mut self, value: f32, time: Duration) -> Option<Beat> {
+        self.envelope.push_back(value);
+        if self.envelope.len() > self.window {
+            self.envelope.pop_front();
+        }
+        self.since_tick += 1;
+
+        let (lag_min, lag_max) = self.lag_range();
+        if lag_min < 1.0 || self.envelope.len() < self.needed_window() {
+            return None;
+        }
+        let interval = ((ESTIMATE_INTERVAL * self.frame_rate) as usize).max(1);
+        if self.period.is_none() || self.since_estimate >= interval {
+            self.period = self.estimate_period(lag_min as usize, lag_max as usize);
+            self.since_estimate = 0;
+        }
+        self.since_estimate += 1;
+        let period = self.period?;
+        self.bpm = 60.0 * self.frame_rate / period;
+        let target = self.estimate_phase(period);
+
+        self.phase += 1.0 / period;
+        if self.locked {
+            let error = (target - self.phase + 0.5).rem_euclid(1.0) - 0.5;
+            self.phase += PHASE_GAIN * error;
+        } else {
+            self.phase = target;
+            self.locked = true;
+        }
+
+        if self.phase < 0.0 {
+            self.phase += 1.0;
+        }
+        if self.phase >= 1.0 {
+            self.phase -= 1.0;
+            // Phase corrections can't tick twice in the same beat
+            if self.since_tick as f32 >= period / 2.0 {
+                self.since_tick = 0;
+                return Some(Beat {
+                    time,
+                    bpm: self.bpm,
+                });
+            }
+        }
+        None
+    }
+
+    /// Same tempo range and window duration for an envelope at `frame_rate`, without history.
+    ///
+    /// The window grows if it can't hold two beats at the new rate. At a rate too low for a
+    /// beat at the fastest tempo to last a frame, the tracker never estimates a tempo.
+    pub(crate) fn at_frame_rate(&self, frame_rate: f32) -> BeatTracker {
+        let seconds = self.window as f32 / self.frame_rate;
+        let mut tracker = BeatTracker {
+            frame_rate,
+            min_bpm: self.min_bpm,
+            max_bpm: self.max_bpm,
+            window: (seconds * frame_rate).ceil() as usize,
+            envelope: VecDeque::new(),
+            centered: Vec::new(),
+            scores: Vec::new(),
+            bpm: 0.0,
+            period: None,
+            since_estimate: 0,
+            phase: 0.0,
+            locked: false,
+            since_tick: 0,
+        };
+        tracker.window = tracker.window.max(tracker.needed_window());
+        tracker
+    }
+
+    /// Panics if a beat at the fastest tempo is shorter than a frame, or if the window is
+    /// shorter than the envelope [`BeatTracker::push`] needs to estimate the slowest tempo.
+    fn check_window(&self) {
+        let (lag_min, _) = self.lag_range();
+        if lag_min < 1.0 {
+            panic!(
+                "frame rate ({}) is too low for beats at {} BPM",
+                self.frame_rate, self.max_bpm
+            )
+        }
+        let needed = self.needed_window();
+        if self.window < needed {
+            panic!(
+                "window of {} frames is shorter than the {} frames needed for {} BPM",
+                self.window, needed, self.min_bpm
+            )
+        }
+    }
+
+    /// Envelope frames needed to estimate the slowest tempo.
+    fn needed_window(&self) -> usize {
+        let (_, lag_max) = self.lag_range();
+        2 * lag_max as usize + 3
+    }
+
+    /// Range of beat periods in frames.
+    fn lag_range(&self) -> (f32, f32) {
+        (
+            (60.0 * self.frame_rate / self.max_bpm).floor(),
+            (60.0 * self.frame_rate / self.min_bpm).ceil(),
+        )
+    }
+
+    /// Beat period in frames, interpolated between lags.
+    fn estimate_period(&mut self, lag_min: usize, lag_max: usize) -> Option<f32> {
+        let mean = self.envelope.iter().sum::<f32>() / self.envelope.len() as f32;
+        // Blurred, so onsets landing on neighbouring frames from beat to beat still correlate
+        let len = self.envelope.len();
+        let envelope = &self.envelope;
+        self.centered.clear();
+        self.centered.extend((0..len).map(|i| {
+            let previous = envelope[i.saturating_sub(1)];
+            let next = envelope[(i + 1).min(len - 1)];
+            0.25 * previous + 0.5 * envelope[i] + 0.25 * next - mean
+        }));
+        let centered = &self.centered;
+        let autocorrelation = |lag: usize| -> f32 {
+            centered[lag..]
+                .iter()
+                .zip(centered.iter())
+                .map(|(a, b)| a * b)
+                .sum()
+        };
+
+        self.scores.clear();
+        self.scores.extend(
+            (lag_min - 1..=lag_max + 1)
+                .map(|lag| autocorrelation(lag) + 0.5 * autocorrelation(2 * lag)),
+        );
+        let scores = &self.scores;
+        let peak = |range: std::ops::Range<usize>| {
+            range.max_by(|&a: &usize, &b: &usize| scores[a].total_cmp(&scores[b]))
+        };
+        let mut best = peak(1..scores.len() - 1)?;
+        if scores[best] <= 0.0 {
+            return None;
+        }
+        // A fractional period spreads its peak over two lags while its double may fall on
+        // a single one, so a strong peak at half the lag is the actual beat
+        let half = (lag_min - 1 + best) / 2;
+        if half > lag_min {
+            let start = half - lag_min;
+            if let Some(faster) = peak(start..start + 3) {
+                if scores[faster] >= OCTAVE_RATIO * scores[best] {
+                    best = faster;
+                }
+            }
+        }
+        let (a, b, c) = (scores[best - 1], scores[best], scores[best + 1]);
+        let denominator = a - 2.0 * b + c;
+        let offset = if denominator < 0.0 {
+            (0.5 * (a - c) / denominator).clamp(-0.5, 0.5)
+        } else {
+            0.0
+        };
+        Some((lag_min - 1 + best) as f32 + offset)
+    }
+
+    /// Phase of the newest frame within a beat of `period` frames.
+    fn estimate_phase(&self, period: f32) -> f32 {
+        let newest = self.envelope.len() - 1;
+        // Mean envelope on the comb whose teeth are `offset` frames before each beat
+        let comb = |offset: usize| -> f32 {
+            let mut sum = 0.0;
+            let mut count = 0;
+            let mut k = 0.0;
+            while let Some(index) = newest.checked_sub(offset + (k * period).round() as usize) {
+                sum += self.envelope[index];
+                count += 1;
+                k += 1.0;
+            }
+            sum / count.max(1) as f32
+        };
+        let teeth = period.ceil() as usize;
+        let best = (0..teeth)
+            .max_by(|&a, &b| comb(a).total_cmp(&comb(b)))
+            .unwrap_or(0);
+        let (a, b, c) = (
+            comb((best + teeth - 1) % teeth),
+            comb(best),
+            comb((best + 1) % teeth),
+        );
+        let denominator = a - 2.0 * b + c;
+        let offset = if denominator < 0.0 {
+            (0.5 * (a - c) / denominator).clamp(-0.5, 0.5)
+        } else {
+            0.0
+        };
+        // The last beat was `best + offset` frames ago
+        ((best as f32 + offset) / period).rem_euclid(1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fft_analizer::FrequencySpectrum;
+
+    use super::*;
+    use crate::onset::OnsetDetector;
+
+    const SAMPLE_RATE: f32 = 8000.0;
+    const FRAME: usize = 512;
+    const HOP: usize = 128;
+
+    fn click_track(bpm: f32, seconds: f32) -> Vec<f32> {
+        let mut signal = vec![0.0; (seconds * SAMPLE_RATE) as usize];
+        let period = 60.0 / bpm * SAMPLE_RATE;
+        let mut click = 0.0;
+        while (click as usize) + 40 < signal.len() {
+            signal[click as usize..click as usize + 40].fill(1.0);
+            click += period;
+        }
+        signal
+    }
+
+    /// Returns the tracker after the whole signal and the beats emitted on the way.
+    fn track(signal: &[f32]) -> (BeatTracker, Vec<Beat>) {
+        let mut fs = FrequencySpectrum::new(FRAME, 1).with_sample_rate(SAMPLE_RATE);
+        let mut onsets = OnsetDetector::default();
+        let mut tracker = BeatTracker::new(SAMPLE_RATE / HOP as f32);
+        let mut beats = Vec::new();
+        for start in (0..=signal.len() - FRAME).step_by(HOP) {
+            let center = (start + FRAME / 2) as f32 / SAMPLE_RATE;
+            let time = Duration::from_secs_f32(center);
+            onsets.process(fs.complex_spectrum(&signal[start..start + FRAME]), time);
+            beats.extend(tracker.push(onsets.value(), time));
+        }
+        (tracker, beats)
+    }
+
+    #[test]
+    fn estimates_the_tempo_of_click_tracks() {
+        for bpm in [72.0, 90.0, 120.0, 140.0, 174.0] {
+            let (tracker, _) = track(&click_track(bpm, 10.0));
+            assert!(
+                (tracker.bpm() - bpm).abs() < 1.5,
+                "{} != {}",
+                tracker.bpm(),
+                bpm
+            );
+        }
+    }
+
+    #[test]
+    fn beats_fall_on_the_clicks() {
+        let bpm = 120.0;
+        let (_, beats) = track(&click_track(bpm, 12.0));
+        let period = 60.0 / bpm;
+        let settled: Vec<f32> = beats
+            .iter()
+            .map(|beat| beat.time.as_secs_f32())
+            .filter(|&t| t > 4.0)
+            .collect();
+        assert!(settled.len() >= 14, "{:?}", settled);
+        for pair in settled.windows(2) {
+            assert!((pair[1] - pair[0] - period).abs() < 0.05, "{:?}", pair);
+        }
+        // Onset frames are timestamped at their centre, a frame can lag the click by its length
+        let frame = FRAME as f32 / SAMPLE_RATE;
+        for t in settled {
+            let error = (t / period).fract() * period;
+            let error = error.min(period - error);
+            assert!(
+                error <= frame,
+                "beat at {} is {} s off the clicks",
+                t,
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn phase_rises_between_beats() {
+        let (mut tracker, _) = track(&click_track(120.0, 6.0));
+        // 10 frames is a third of a beat at 120 BPM, so the phase wraps at most once
+        let mut phases = vec![tracker.beat_phase()];
+        let mut beats = 0;
+        for frame in 0..10 {
+            let time = Duration::from_secs_f32((frame * HOP) as f32 / SAMPLE_RATE);
+            beats += tracker.push(0.0, time).iter().count();
+            phases.push(tracker.beat_phase());
+        }
+        let drops = phases.windows(2).filter(|pair| pair[1] < pair[0]).count();
+        assert_eq!(drops, beats, "{:?}", phases);
+        assert!(beats <= 1);
+        assert!(phases.iter().all(|phase| (0.0..1.0).contains(phase)));
+    }
+
+    #[test]
+    #[should_panic]
+    fn window_must_hold_two_beats() {
+        // Two beats at 60 BPM take 2 seconds
+        let _ = BeatTracker::new(100.0).with_window(Duration::from_millis(1500));
+    }
+
+    #[test]
+    #[should_panic]
+    fn beats_must_last_a_frame() {
+        // A beat at 180 BPM lasts a third of a second
+        let _ = BeatTracker::new(2.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn fastest_tempo_must_last_a_frame() {
+        let _ = BeatTracker::new(10.0).with_tempo_range(60.0, 900.0);
+    }
+
+    #[test]
+    fn silence_has_no_tempo() {
+        let (tracker, beats) = track(&vec![0.0; 8 * SAMPLE_RATE as usize]);
+        assert_eq!(tracker.bpm(), 0.0);
+        assert!(beats.is_empty());
+    }
+}
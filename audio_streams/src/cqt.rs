@@ -0,0 +1,176 @@
+use std::time::Duration;
+
+use fft_analizer::cqt::ConstantQ;
+use ringbuf::traits::Consumer;
+
+use crate::{smoothing::Smoother, Analyzer, HeapConsumerF32};
+
+/// Constant-Q transform over a mono sample stream.
+///
+/// An alternative to [`crate::bandpass::FilterBankConsumer`]: with 12 bins per octave
+/// from the same `f_min` it reports the same semitone spaced bands, computed with FFT
+/// kernels instead of a biquad per band.
+pub struct ConstantQConsumer<T: Consumer<Item = f32>> {
+    consumer: T,
+    cqt: ConstantQ,
+    /// Samples between consecutive frames
+    hop: usize,
+    /// Samples read since the last frame
+    pending: Vec<f32>,
+    /// Magnitude of each bin of the last frame
+    bands: Vec<f32>,
+    smoothed: Vec<f32>,
+    smoother: Smoother,
+    /// Whether the last update computed a frame
+    ready: bool,
+    /// Number of frames computed since creation
+    frames: u64,
+}
+
+pub type AudioConsumerConstantQF32 = ConstantQConsumer<HeapConsumerF32>;
+
+impl<T: Consumer<Item = f32>> ConstantQConsumer<T> {
+    /// Computes `cqt` every 1024 samples.
+    pub fn new(consumer: T, cqt: ConstantQ) -> Self {
+        let len = cqt.len();
+        ConstantQConsumer {
+            consumer,
+            cqt,
+            hop: 1024,
+            pending: Vec::new(),
+            bands: vec![0.0; len],
+            smoothed: vec![0.0; len],
+            smoother: Smoother::default(),
+            ready: false,
+            frames: 0,
+        }
+    }
+
+    /// Computes a new frame every `hop` samples.
+    ///
+    /// # Panics
+    ///
+    /// If `hop` is 0.
+    pub fn with_hop(mut self, hop: usize) -> Self {
+        if hop == 0 {
+            panic!("hop ({}) must be at least 1", hop)
+        }
+        self.hop = hop;
+        self
+    }
+
+    /// Replaces the smoothing of the bands, a time constant of 250 ms by default.
+    pub fn with_smoother(mut self, smoother: Smoother) -> Self {
+        self.smoother = smoother;
+        self
+    }
+
+    pub fn hop(&self) -> usize {
+        self.hop
+    }
+
+    /// Number of frames computed since creation.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Reads every available sample, computes a frame each time `hop` samples arrived
+    /// and smooths the bands over `milis`.
+    pub fn update(&mut self, milis: Duration) {
+        self.ready = false;
+        while let Some(sample) = self.consumer.try_pop() {
+            self.pending.push(sample);
+            if self.pending.len() == self.hop {
+                self.bands.copy_from_slice(self.cqt.process(&self.pending));
+                self.pending.clear();
+                self.frames += 1;
+                self.ready = true;
+            }
+        }
+        self.smoother.smooth(&mut self.smoothed, &self.bands, milis);
+    }
+}
+
+impl<T: Consumer<Item = f32>> Analyzer for ConstantQConsumer<T> {
+    fn update(&mut self, elapsed: Duration) {
+        ConstantQConsumer::update(self, elapsed);
+    }
+
+    fn frame_ready(&self) -> bool {
+        self.ready
+    }
+
+    /// Magnitude of each bin, a full-scale sine at a bin frequency reads 1.0.
+    fn bands(&self) -> &[f32] {
+        &self.bands
+    }
+
+    fn smoothed(&self) -> &[f32] {
+        &self.smoothed
+    }
+
+    fn band_frequencies(&self) -> &[f32] {
+        self.cqt.frequencies()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ringbuf::{traits::*, HeapRb};
+
+    use super::*;
+    use crate::{bandpass::FilterBankConsumer, test_utils::sine};
+
+    #[test]
+    fn computes_one_frame_per_hop() {
+        let (mut prod, cons) = HeapRb::<f32>::new(8192).split();
+        let cqt = ConstantQ::new(8000.0, 110.0, 12, 3);
+        let mut consumer = ConstantQConsumer::new(cons, cqt).with_hop(512);
+        prod.push_slice(&sine(440.0, 8000.0, 1000));
+        consumer.update(Duration::ZERO);
+        assert_eq!(consumer.frames(), 1);
+        assert!(consumer.frame_ready());
+        prod.push_slice(&sine(440.0, 8000.0, 24));
+        consumer.update(Duration::ZERO);
+        assert_eq!(consumer.frames(), 2);
+        consumer.update(Duration::ZERO);
+        assert!(!consumer.frame_ready());
+    }
+
+    #[test]
+    fn swaps_with_the_filter_bank() {
+        let samples = sine(440.0, 8000.0, 8192);
+        let (mut cqt_prod, cqt_cons) = HeapRb::<f32>::new(8192).split();
+        let (mut bank_prod, bank_cons) = HeapRb::<f32>::new(8192).split();
+        let cqt = ConstantQ::with_bins(8000.0, 110.0, 12, 48);
+        let mut analyzers: Vec<(Box<dyn Analyzer>, _)> = vec![
+            (
+                Box::new(ConstantQConsumer::new(cqt_cons, cqt)),
+                &mut cqt_prod,
+            ),
+            (
+                Box::new(FilterBankConsumer::<1024, 48, 4, _>::new(
+                    bank_cons, 8000.0, 110.0, 1760.0,
+                )),
+                &mut bank_prod,
+            ),
+        ];
+        for (analyzer, prod) in analyzers.iter_mut() {
+            prod.push_slice(&samples);
+            analyzer.update(Duration::from_millis(16));
+            assert!(analyzer.frame_ready());
+            assert_eq!(analyzer.smoothed().len(), analyzer.bands().len());
+            // A4 is two octaves above the lowest band
+            let bands = analyzer.bands();
+            let loudest = (0..bands.len())
+                .max_by(|&a, &b| bands[a].total_cmp(&bands[b]))
+                .unwrap();
+            assert_eq!(loudest, 24);
+        }
+        let (cqt, bank) = (&analyzers[0].0, &analyzers[1].0);
+        assert_eq!(cqt.band_frequencies().len(), bank.band_frequencies().len());
+        for (a, b) in cqt.band_frequencies().iter().zip(bank.band_frequencies()) {
+            assert!((a - b).abs() < 1e-3 * b, "{} != {}", a, b);
+        }
+    }
+}
@@ -1,7 +1,22 @@
-use fft_analizer::FrequencySpectrum;
+use beat::{Beat, BeatTracker};
+use fft_analizer::descriptors::SpectralDescriptors;
+use onset::{Onset, OnsetDetector};
 use ringbuf::{storage::Heap, traits::*, wrap::caching::Caching, SharedRb};
+use runtime::{FftConfig, RuntimeFftConsumer};
+use smoothing::Smoother;
 use std::{sync::Arc, time::Duration};
+mod analyzer;
 pub mod bandpass;
+pub mod beat;
+pub mod cqt;
+pub mod onset;
+pub mod pitch;
+pub mod runtime;
+pub mod smoothing;
+#[cfg(test)]
+mod test_utils;
+
+pub use analyzer::Analyzer;
 
 pub struct InputModel<T: Producer<Item = f32>> {
     pub producer: T,
@@ -10,71 +25,405 @@ pub struct InputModel<T: Producer<Item = f32>> {
 /// This monster is derived from the (HeapRb::<f32>).split() return type
 pub type AudioProducerF32 = InputModel<Caching<Arc<SharedRb<Heap<f32>>>, true, false>>;
 /// This monster is derived from the (HeapRb::<f32>).split() return type
+pub type HeapConsumerF32 = Caching<Arc<SharedRb<Heap<f32>>>, false, true>;
 pub type AudioConsumerF32<const IB_LEN: usize, const FB_LEN: usize, const DELTA: usize> =
-    FftConsumer<IB_LEN, FB_LEN, DELTA, Caching<Arc<SharedRb<Heap<f32>>>, false, true>>;
+    FftConsumer<IB_LEN, FB_LEN, DELTA, HeapConsumerF32>;
 
+/// Sliding STFT over an interleaved sample stream with sizes fixed at compile time.
+///
+/// Keeps the last `IB_LEN` samples in a circular history and computes a new spectrum
+/// every `hop` samples, so consecutive frames overlap by `IB_LEN - hop` samples.
+/// Wraps a [`RuntimeFftConsumer`] and copies its results into arrays when an update
+/// computes a frame.
 pub struct FftConsumer<
     const IB_LEN: usize,
     const FB_LEN: usize,
     const DELTA: usize,
     T: Consumer<Item = f32>,
 > {
-    /// Consumer to read from shared buffer
-    consumer: T,
-    /// Input samples
+    inner: RuntimeFftConsumer<T>,
+    /// Circular history of the input samples as of the last frame, see
+    /// [`fft_analizer::Framer::history`]
     pub samples: [f32; IB_LEN],
     /// Processed frequencies
     pub frequencies: [f32; FB_LEN],
-    /// Smoothed frequencies
+    /// Frequencies smoothed on every update, see [`FftConsumer::with_smoother`]
     pub smoothed: [f32; FB_LEN],
-    /// Read index
-    index: usize,
-    fs: FrequencySpectrum,
+    /// Descriptors of the last frame, computed from its linear magnitudes
+    pub descriptors: SpectralDescriptors,
 }
 
 impl<const IB_LEN: usize, const FB_LEN: usize, const DELTA: usize, T: Consumer<Item = f32>>
     FftConsumer<IB_LEN, FB_LEN, DELTA, T>
 {
+    /// Creates a consumer without overlap, a new spectrum is computed every `IB_LEN` samples.
+    ///
+    /// # Panics
+    ///
+    /// If `DELTA` is 0 or the sizes are rejected by [`FftConfig::validate`].
     pub fn new(consumer: T, channels: u16) -> Self {
+        let inner = FftConfig::new(IB_LEN, channels)
+            .with_bands(FB_LEN)
+            .with_smoothing(1.0 / DELTA as f32, 1.0 / DELTA as f32)
+            .build(consumer)
+            .unwrap_or_else(|error| panic!("{}", error));
         FftConsumer {
-            consumer,
+            inner,
             samples: [0.0; IB_LEN],
             frequencies: [0.0; FB_LEN],
             smoothed: [0.0; FB_LEN],
-            index: 0,
-            fs: FrequencySpectrum::new(IB_LEN, channels),
+            descriptors: SpectralDescriptors::default(),
+        }
+    }
+
+    /// Panics if `config` is rejected by [`FftConfig::validate`].
+    fn reconfigured(mut self, config: FftConfig) -> Self {
+        if let Err(error) = self.inner.reconfigure(config) {
+            panic!("{}", error)
+        }
+        self
+    }
+
+    /// Sets the sample rate used to express descriptors in Hz.
+    ///
+    /// # Panics
+    ///
+    /// If `sample_rate` is not finite and positive.
+    pub fn with_sample_rate(self, sample_rate: f32) -> Self {
+        let config = self.inner.config().clone().with_sample_rate(sample_rate);
+        self.reconfigured(config)
+    }
+
+    /// Replaces the smoothing of [`FftConsumer::smoothed`], a time constant of `1 / DELTA`
+    /// seconds by default.
+    pub fn with_smoother(self, smoother: Smoother) -> Self {
+        let config = self
+            .inner
+            .config()
+            .clone()
+            .with_smoothing(smoother.attack(), smoother.release())
+            .with_smoothing_mode(smoother.mode());
+        self.reconfigured(config)
+    }
+
+    /// Sets the percentage of energy used for the rolloff descriptor, 85 by default.
+    ///
+    /// # Panics
+    ///
+    /// If `percent` is not in `0.0..=100.0`.
+    pub fn with_rolloff(mut self, percent: f32) -> Self {
+        self.inner = self.inner.with_rolloff(percent);
+        self
+    }
+
+    /// Computes a new spectrum every `hop` interleaved samples, e.g. `IB_LEN / 4` for 75% overlap.
+    ///
+    /// # Panics
+    ///
+    /// If `hop` is 0, greater than `IB_LEN` or not a multiple of the number of channels.
+    pub fn with_hop(self, hop: usize) -> Self {
+        let config = self.inner.config().clone().with_hop(hop);
+        self.reconfigured(config)
+    }
+
+    /// Runs `detector` on every frame, see [`FftConsumer::drain_onsets`].
+    pub fn with_onset_detector(mut self, detector: OnsetDetector) -> Self {
+        self.inner = self.inner.with_onset_detector(detector);
+        self
+    }
+
+    /// Tracks the beat of the onset detection function, see [`FftConsumer::drain_beats`].
+    ///
+    /// `tracker` must be created with [`FftConsumer::frame_rate`]. Uses the detector set with
+    /// [`FftConsumer::with_onset_detector`], or a default one.
+    pub fn with_beat_tracker(mut self, tracker: BeatTracker) -> Self {
+        self.inner = self.inner.with_beat_tracker(tracker);
+        self
+    }
+
+    /// Number of frames computed per second of input.
+    pub fn frame_rate(&self) -> f32 {
+        self.inner.frame_rate()
+    }
+
+    /// Estimated tempo, 0 without a beat tracker or until it has seen enough input.
+    pub fn bpm(&self) -> f32 {
+        self.inner.bpm()
+    }
+
+    /// Position within the current beat, from 0 on the beat to 1 just before the next one.
+    pub fn beat_phase(&self) -> f32 {
+        self.inner.beat_phase()
+    }
+
+    pub fn hop(&self) -> usize {
+        self.inner.config().hop()
+    }
+
+    /// Removes and returns the onsets detected since the last call, oldest first.
+    ///
+    /// Onsets accumulate until drained, so call it after every [`FftConsumer::update`].
+    pub fn drain_onsets(&mut self) -> std::vec::Drain<'_, Onset> {
+        self.inner.drain_onsets()
+    }
+
+    /// Removes and returns the beats emitted since the last call, oldest first.
+    pub fn drain_beats(&mut self) -> std::vec::Drain<'_, Beat> {
+        self.inner.drain_beats()
+    }
+
+    /// Number of spectra computed since creation.
+    pub fn frames(&self) -> u64 {
+        self.inner.frames()
+    }
+
+    // Updates the frequencies buffer by reading from input buffer and writing to frequencies array
+    pub fn update(&mut self, milis: Duration) {
+        self.inner.update(milis);
+        if self.inner.frame_ready() {
+            self.samples.copy_from_slice(self.inner.samples());
+            self.frequencies.copy_from_slice(self.inner.bands());
+            self.descriptors = *self.inner.descriptors();
+        }
+        self.smoothed.copy_from_slice(self.inner.smoothed());
+    }
+}
+
+impl<const IB_LEN: usize, const FB_LEN: usize, const DELTA: usize, T: Consumer<Item = f32>> Analyzer
+    for FftConsumer<IB_LEN, FB_LEN, DELTA, T>
+{
+    fn update(&mut self, elapsed: Duration) {
+        FftConsumer::update(self, elapsed);
+    }
+
+    fn frame_ready(&self) -> bool {
+        self.inner.frame_ready()
+    }
+
+    fn bands(&self) -> &[f32] {
+        &self.frequencies
+    }
+
+    fn smoothed(&self) -> &[f32] {
+        &self.smoothed
+    }
+
+    fn band_frequencies(&self) -> &[f32] {
+        self.inner.band_frequencies()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fft_analizer::FrequencySpectrum;
+    use ringbuf::HeapRb;
+
+    use super::*;
+    use crate::test_utils::{ramp, sine};
+
+    #[test]
+    fn computes_one_frame_per_hop_within_a_single_update() {
+        let (mut prod, cons) = HeapRb::<f32>::new(64).split();
+        let mut consumer = FftConsumer::<16, 8, 1, _>::new(cons, 1).with_hop(4);
+        let samples = ramp(40);
+        prod.push_slice(&samples);
+        consumer.update(Duration::ZERO);
+        // First frame once the history is full, then one every 4 samples
+        assert_eq!(consumer.frames(), 7);
+
+        let mut fs = FrequencySpectrum::new(16, 1);
+        assert_eq!(
+            &consumer.frequencies[..],
+            fs.frequency_spectrum(&samples[24..40])
+        );
+    }
+
+    #[test]
+    fn frames_overlap_across_updates() {
+        let (mut prod, cons) = HeapRb::<f32>::new(64).split();
+        let mut consumer = FftConsumer::<16, 8, 1, _>::new(cons, 1).with_hop(4);
+        let samples = ramp(30);
+        for chunk in samples.chunks(3) {
+            prod.push_slice(chunk);
+            consumer.update(Duration::ZERO);
         }
+        // Frames end at samples 16, 20, 24 and 28
+        assert_eq!(consumer.frames(), 4);
+
+        let mut fs = FrequencySpectrum::new(16, 1);
+        assert_eq!(
+            &consumer.frequencies[..],
+            fs.frequency_spectrum(&samples[12..28])
+        );
+    }
+
+    #[test]
+    fn arrays_keep_the_last_frame_between_frames() {
+        let (mut prod, cons) = HeapRb::<f32>::new(64).split();
+        let mut consumer = FftConsumer::<16, 8, 1, _>::new(cons, 1);
+        prod.push_slice(&ramp(16));
+        consumer.update(Duration::ZERO);
+        let (samples, frequencies) = (consumer.samples, consumer.frequencies);
+        prod.push_slice(&[1.0; 8]);
+        consumer.update(Duration::from_millis(10));
+        assert!(!consumer.frame_ready());
+        assert_eq!(consumer.samples, samples);
+        assert_eq!(consumer.frequencies, frequencies);
+        assert_eq!(&consumer.smoothed[..], Analyzer::smoothed(&consumer.inner));
     }
 
-    fn read_samples(&mut self) {
-        while let Some(sample) = self.consumer.try_pop() {
-            self.samples[self.index] = sample;
-            self.index += 1;
-            if self.index == IB_LEN {
-                break;
-            }
+    #[test]
+    fn default_hop_does_not_overlap() {
+        let (mut prod, cons) = HeapRb::<f32>::new(64).split();
+        let mut consumer = FftConsumer::<16, 8, 1, _>::new(cons, 1);
+        prod.push_slice(&ramp(40));
+        consumer.update(Duration::ZERO);
+        assert_eq!(consumer.frames(), 2);
+    }
+
+    #[test]
+    fn descriptors_follow_the_input() {
+        let (mut prod, cons) = HeapRb::<f32>::new(2048).split();
+        let mut consumer = FftConsumer::<1024, 64, 1, _>::new(cons, 1).with_sample_rate(8000.0);
+        // 1 kHz is exactly on bin 128
+        let sine = sine(1000.0, 8000.0, 1024);
+        prod.push_slice(&sine);
+        consumer.update(Duration::ZERO);
+        let tonal = consumer.descriptors;
+        assert!((tonal.centroid - 1000.0).abs() < 20.0, "{}", tonal.centroid);
+        assert!(tonal.flatness < 0.05, "{}", tonal.flatness);
+        assert_eq!(tonal.flux, 0.0);
+
+        prod.push_slice(&[0.0; 1024]);
+        consumer.update(Duration::ZERO);
+        assert_eq!(consumer.descriptors.centroid, 0.0);
+        assert_eq!(consumer.descriptors.flux, 0.0);
+
+        prod.push_slice(&sine);
+        consumer.update(Duration::ZERO);
+        assert!(consumer.descriptors.flux > 0.0);
+    }
+
+    #[test]
+    fn onsets_are_drained_after_update() {
+        let (mut prod, cons) = HeapRb::<f32>::new(4096).split();
+        let mut consumer = FftConsumer::<512, 64, 1, _>::new(cons, 2)
+            .with_sample_rate(8000.0)
+            .with_hop(256)
+            .with_onset_detector(OnsetDetector::default());
+        // Stereo click train, one click every 0.25 s
+        let mut samples = vec![0.0; 2 * 8000];
+        for click in [2000, 4000, 6000] {
+            samples[2 * click..2 * click + 40].fill(1.0);
+        }
+        let mut times = Vec::new();
+        for chunk in samples.chunks(1000) {
+            prod.push_slice(chunk);
+            consumer.update(Duration::ZERO);
+            times.extend(
+                consumer
+                    .drain_onsets()
+                    .map(|onset| onset.time.as_secs_f32()),
+            );
+        }
+        assert_eq!(times.len(), 3, "{:?}", times);
+        for (time, expected) in times.iter().zip([0.25, 0.5, 0.75]) {
+            assert!((time - expected).abs() < 0.032, "{} != {}", time, expected);
         }
+        assert_eq!(consumer.drain_onsets().count(), 0);
     }
-    fn process_samples(&mut self, milis: Duration) {
-        if self.index < IB_LEN - 1 {
-            return;
+
+    #[test]
+    fn beats_follow_a_click_track() {
+        let (mut prod, cons) = HeapRb::<f32>::new(4096).split();
+        let consumer = FftConsumer::<512, 64, 1, _>::new(cons, 1)
+            .with_sample_rate(8000.0)
+            .with_hop(128);
+        let tracker = BeatTracker::new(consumer.frame_rate());
+        let mut consumer = consumer.with_beat_tracker(tracker);
+        // 100 BPM for 8 s
+        let mut samples = vec![0.0; 8 * 8000];
+        for click in (0..samples.len() - 40).step_by(4800) {
+            samples[click..click + 40].fill(1.0);
         }
-        let ff = self.fs.frequency_spectrum(&mut self.samples);
-        for i in 0..self.frequencies.len() {
-            self.frequencies[i] = ff[i];
+        let mut beats = Vec::new();
+        for chunk in samples.chunks(1000) {
+            prod.push_slice(chunk);
+            consumer.update(Duration::ZERO);
+            beats.extend(consumer.drain_beats());
         }
-        let m = (milis.as_nanos() / 1_000_000) as f64;
-        for i in 0..self.frequencies.len() {
-            self.smoothed[i] +=
-                (ff[i] - self.smoothed[i]) as f32 * (m / 1000.0) as f32 * DELTA as f32;
+        assert!((consumer.bpm() - 100.0).abs() < 1.5, "{}", consumer.bpm());
+        assert!((0.0..1.0).contains(&consumer.beat_phase()));
+        assert!(beats.len() >= 5, "{:?}", beats);
+        // Onsets are only kept when a detector was requested
+        assert_eq!(consumer.drain_onsets().count(), 0);
+    }
+
+    #[test]
+    fn smooths_between_frames() {
+        let (mut prod, cons) = HeapRb::<f32>::new(64).split();
+        let mut consumer = FftConsumer::<16, 8, 4, _>::new(cons, 1);
+        prod.push_slice(&ramp(16));
+        consumer.update(Duration::ZERO);
+        assert_eq!(consumer.smoothed, [0.0; 8]);
+
+        // No new frame, the smoothed bands still follow the last one
+        consumer.update(Duration::from_millis(250));
+        let alpha = 1.0 - (-1.0f32).exp();
+        for (smoothed, band) in consumer.smoothed.iter().zip(consumer.frequencies.iter()) {
+            assert!((smoothed - alpha * band).abs() < 1e-6);
         }
+    }
 
-        self.index = 0;
+    #[test]
+    fn filter_bank_frames_are_complete() {
+        let (mut prod, cons) = HeapRb::<f32>::new(256).split();
+        let mut filter_bank =
+            bandpass::FilterBankConsumer::<64, 8, 1, _>::new(cons, 8000.0, 440.0, 880.0);
+        prod.push_slice(&ramp(63));
+        filter_bank.update(Duration::ZERO);
+        assert!(!filter_bank.frame_ready());
+        prod.push_slice(&ramp(1));
+        filter_bank.update(Duration::ZERO);
+        assert!(filter_bank.frame_ready());
     }
 
-    // Updates the frequencies buffer by reading from input buffer and writing to frequencies array
-    pub fn update(&mut self, milis: Duration) {
-        self.read_samples();
-        self.process_samples(milis);
+    #[test]
+    fn analyzers_are_interchangeable() {
+        let (mut fft_prod, fft_cons) = HeapRb::<f32>::new(256).split();
+        let (mut bank_prod, bank_cons) = HeapRb::<f32>::new(256).split();
+        let mut analyzers: Vec<(Box<dyn Analyzer>, _)> = vec![
+            (
+                Box::new(FftConsumer::<64, 8, 1, _>::new(fft_cons, 1).with_sample_rate(8000.0)),
+                &mut fft_prod,
+            ),
+            (
+                Box::new(bandpass::FilterBankConsumer::<64, 8, 1, _>::new(
+                    bank_cons, 8000.0, 440.0, 880.0,
+                )),
+                &mut bank_prod,
+            ),
+        ];
+        for (analyzer, prod) in analyzers.iter_mut() {
+            assert_eq!(analyzer.bands().len(), analyzer.band_frequencies().len());
+            assert_eq!(analyzer.smoothed().len(), analyzer.bands().len());
+
+            prod.push_slice(&ramp(32));
+            analyzer.update(Duration::from_millis(16));
+            assert!(!analyzer.frame_ready());
+            prod.push_slice(&ramp(32));
+            analyzer.update(Duration::from_millis(16));
+            assert!(analyzer.frame_ready());
+            assert!(analyzer.bands().iter().any(|&band| band > 0.0));
+        }
+        assert_eq!(analyzers[0].0.band_frequencies()[0], 125.0);
+        assert_eq!(analyzers[1].0.band_frequencies()[0], 440.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn hop_must_be_a_multiple_of_channels() {
+        let (_, cons) = HeapRb::<f32>::new(64).split();
+        let _ = FftConsumer::<16, 4, 1, _>::new(cons, 2).with_hop(3);
     }
 }
@@ -0,0 +1,301 @@
+use std::{collections::VecDeque, time::Duration};
+
+use fft_analizer::Complex;
+
+/// How the change between consecutive spectra is measured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DetectionFunction {
+    /// Sum of the magnitude increases of every bin.
+    #[default]
+    SpectralFlux,
+    /// Energy weighted by bin index, so broadband attacks stand out over tonal content.
+    HighFrequencyContent,
+    /// Distance between each bin and its prediction from the two previous frames,
+    /// reacts to phase changes as well as magnitude changes.
+    ComplexDomain,
+    /// Increase of the frame energy.
+    Energy,
+}
+
+/// A detected note attack.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Onset {
+    /// Time since the first sample, at the centre of the frame holding the attack
+    pub time: Duration,
+    /// Value of the detection function at the onset
+    pub strength: f32,
+}
+
+/// Onset detector fed with one spectrum per frame.
+///
+/// A frame is an onset when its detection function value is a local maximum above
+/// `offset + multiplier * median` of the last values, and at least the refractory
+/// period passed since the previous onset. Local maxima need the next frame, so onsets
+/// are reported one frame late with the time of the frame they belong to.
+#[derive(Debug, Clone)]
+pub struct OnsetDetector {
+    function: DetectionFunction,
+    median_len: usize,
+    multiplier: f32,
+    offset: f32,
+    refractory: Duration,
+    /// Bins of the last two frames, newest first
+    previous: Vec<Complex<f32>>,
+    before: Vec<Complex<f32>>,
+    /// Last detection function values, the newest one is the lookahead
+    values: VecDeque<f32>,
+    sorted: Vec<f32>,
+    /// Time of the frame waiting for its lookahead
+    pending: Option<Duration>,
+    last_onset: Option<Duration>,
+}
+
+impl Default for OnsetDetector {
+    fn default() -> Self {
+        OnsetDetector::new(DetectionFunction::default())
+    }
+}
+
+impl OnsetDetector {
+    /// Creates a detector with a median over 8 frames, a multiplier of 1.5,
+    /// an offset of 0.01 and a refractory period of 50 ms.
+    pub fn new(function: DetectionFunction) -> Self {
+        OnsetDetector {
+            function,
+            median_len: 8,
+            multiplier: 1.5,
+            offset: 0.01,
+            refractory: Duration::from_millis(50),
+            previous: Vec::new(),
+            before: Vec::new(),
+            values: VecDeque::new(),
+            sorted: Vec::new(),
+            pending: None,
+            last_onset: None,
+        }
+    }
+
+    /// Number of past detection function values the median is computed over.
+    ///
+    /// # Panics
+    ///
+    /// If `frames` is 0.
+    pub fn with_median_window(mut self, frames: usize) -> Self {
+        if frames == 0 {
+            panic!("median window must hold at least one frame")
+        }
+        self.median_len = frames;
+        self
+    }
+
+    /// Sets the adaptive threshold to `offset + multiplier * median`.
+    pub fn with_threshold(mut self, multiplier: f32, offset: f32) -> Self {
+        self.multiplier = multiplier;
+        self.offset = offset;
+        self
+    }
+
+    /// Minimum time between two onsets.
+    pub fn with_refractory(mut self, refractory: Duration) -> Self {
+        self.refractory = refractory;
+        self
+    }
+
+    pub fn function(&self) -> DetectionFunction {
+        self.function
+    }
+
+    /// Last value of the detection function, 0 before the first frame.
+    pub fn value(&self) -> f32 {
+        self.values.back().copied().unwrap_or(0.0)
+    }
+
+    /// Adds the spectrum of the frame at `time` and returns the onset of the previous frame, if any.
+    ///
+    /// Magnitudes are divided by the number of bins, so thresholds don't depend on the frame length.
+    pub fn process(&mut self, bins: &[Complex<f32>], time: Duration) -> Option<Onset> {
+        let value = self.detection_value(bins);
+        self.before.clone_from(&self.previous);
+        self.previous.clear();
+        self.previous.extend_from_slice(bins);
+        self.pick(value, time)
+    }
+
+    fn detection_value(&self, bins: &[Complex<f32>]) -> f32 {
+        let scale = 1.0 / bins.len().max(1) as f32;
+        let has_previous = self.previous.len() == bins.len();
+        match self.function {
+            DetectionFunction::SpectralFlux if has_previous => bins
+                .iter()
+                .zip(self.previous.iter())
+                .map(|(x, p)| ((x.norm() - p.norm()) * scale).max(0.0))
+                .sum(),
+            DetectionFunction::HighFrequencyContent => bins
+                .iter()
+                .enumerate()
+                .map(|(k, x)| (k + 1) as f32 * scale * (x * scale).norm_sqr())
+                .sum(),
+            DetectionFunction::ComplexDomain if has_previous => {
+                let has_before = self.before.len() == bins.len();
+                bins.iter()
+                    .zip(self.previous.iter())
+                    .enumerate()
+                    .map(|(k, (x, p))| {
+                        let phase = if has_before {
+                            2.0 * p.arg() - self.before[k].arg()
+                        } else {
+                            p.arg()
+                        };
+                        (x - Complex::from_polar(p.norm(), phase)).norm() * scale
+                    })
+                    .sum()
+            }
+            DetectionFunction::Energy if has_previous => {
+                let energy = |bins: &[Complex<f32>]| -> f32 {
+                    bins.iter().map(|x| (x * scale).norm_sqr()).sum()
+                };
+                (energy(bins) - energy(&self.previous)).max(0.0)
+            }
+            // Nothing to compare the first frame with
+            _ => 0.0,
+        }
+    }
+
+    /// Adds `value` as the lookahead of the pending frame and checks whether that frame is an onset.
+    fn pick(&mut self, value: f32, time: Duration) -> Option<Onset> {
+        self.values.push_back(value);
+        if self.values.len() > self.median_len + 1 {
+            self.values.pop_front();
+        }
+        let pending = self.pending.replace(time)?;
+        let len = self.values.len();
+        if len < 3 {
+            return None;
+        }
+        let (before, candidate, after) = (
+            self.values[len - 3],
+            self.values[len - 2],
+            self.values[len - 1],
+        );
+
+        self.sorted.clear();
+        self.sorted.extend(self.values.range(..len - 1));
+        self.sorted.sort_by(f32::total_cmp);
+        let median = self.sorted[self.sorted.len() / 2];
+        let threshold = self.offset + self.multiplier * median;
+
+        let refractory = self
+            .last_onset
+            .is_some_and(|last| pending.saturating_sub(last) < self.refractory);
+        if candidate > threshold && candidate > before && candidate >= after && !refractory {
+            self.last_onset = Some(pending);
+            Some(Onset {
+                time: pending,
+                strength: candidate,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use fft_analizer::FrequencySpectrum;
+
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 8000.0;
+    const FRAME: usize = 512;
+    const HOP: usize = 128;
+
+    /// Runs a detector over `signal` and returns the onset times in seconds.
+    fn detect(detector: &mut OnsetDetector, signal: &[f32]) -> Vec<f32> {
+        let mut fs = FrequencySpectrum::new(FRAME, 1).with_sample_rate(SAMPLE_RATE);
+        let mut onsets = Vec::new();
+        for start in (0..=signal.len() - FRAME).step_by(HOP) {
+            let center = (start + FRAME / 2) as f32 / SAMPLE_RATE;
+            let bins = fs.complex_spectrum(&signal[start..start + FRAME]);
+            if let Some(onset) = detector.process(bins, Duration::from_secs_f32(center)) {
+                onsets.push(onset.time.as_secs_f32());
+            }
+        }
+        onsets
+    }
+
+    /// Decaying 1 kHz bursts starting at `starts` seconds.
+    fn bursts(starts: &[f32], len: usize) -> Vec<f32> {
+        let mut signal = vec![0.0; len];
+        for &start in starts {
+            let start = (start * SAMPLE_RATE) as usize;
+            for (n, s) in signal[start..].iter_mut().enumerate() {
+                let t = n as f32 / SAMPLE_RATE;
+                *s += (2.0 * PI * 1000.0 * t).sin() * (-t * 20.0).exp();
+            }
+        }
+        signal
+    }
+
+    fn assert_onsets(onsets: &[f32], expected: &[f32]) {
+        assert_eq!(onsets.len(), expected.len(), "{:?}", onsets);
+        for (onset, expected) in onsets.iter().zip(expected.iter()) {
+            // Within one frame of the attack
+            assert!(
+                (onset - expected).abs() <= FRAME as f32 / SAMPLE_RATE,
+                "{} != {}",
+                onset,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn every_function_finds_the_attacks() {
+        let starts = [0.25, 0.75, 1.25, 1.75];
+        let signal = bursts(&starts, 18000);
+        for function in [
+            DetectionFunction::SpectralFlux,
+            DetectionFunction::HighFrequencyContent,
+            DetectionFunction::ComplexDomain,
+            DetectionFunction::Energy,
+        ] {
+            let onsets = detect(&mut OnsetDetector::new(function), &signal);
+            assert_onsets(&onsets, &starts);
+        }
+    }
+
+    #[test]
+    fn steady_tone_has_a_single_onset() {
+        let signal: Vec<f32> = (0..16000)
+            .map(|n| {
+                let t = n as f32 / SAMPLE_RATE;
+                if t < 0.5 {
+                    0.0
+                } else {
+                    0.5 * (2.0 * PI * 440.0 * t).sin()
+                }
+            })
+            .collect();
+        let onsets = detect(&mut OnsetDetector::default(), &signal);
+        assert_onsets(&onsets, &[0.5]);
+    }
+
+    #[test]
+    fn refractory_period_merges_close_attacks() {
+        let signal = bursts(&[0.5, 0.6], 8000);
+        let onsets = detect(&mut OnsetDetector::default(), &signal);
+        assert_onsets(&onsets, &[0.5, 0.6]);
+
+        let mut detector = OnsetDetector::default().with_refractory(Duration::from_millis(200));
+        let onsets = detect(&mut detector, &signal);
+        assert_onsets(&onsets, &[0.5]);
+    }
+
+    #[test]
+    fn silence_has_no_onsets() {
+        let onsets = detect(&mut OnsetDetector::default(), &[0.0; 8000]);
+        assert!(onsets.is_empty());
+    }
+}
@@ -0,0 +1,361 @@
+use std::time::Duration;
+
+use fft_analizer::Framer;
+use ringbuf::traits::Consumer;
+
+use crate::HeapConsumerF32;
+
+/// Frames quieter than this mean square are reported as unvoiced.
+const SILENCE: f32 = 1e-8;
+
+/// A fundamental frequency estimate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pitch {
+    /// Fundamental frequency in Hz
+    pub frequency: f32,
+    /// How periodic the frame is, from 0 (noise) to 1 (perfectly periodic)
+    pub clarity: f32,
+    /// Nearest MIDI note, A4 (440 Hz) is 69
+    pub midi_note: u8,
+    /// Offset from `midi_note` in cents, in `-50.0..=50.0`
+    pub cents: f32,
+}
+
+impl Pitch {
+    pub fn new(frequency: f32, clarity: f32) -> Self {
+        let midi = frequency_to_midi(frequency);
+        let midi_note = midi.round().clamp(0.0, 127.0);
+        Pitch {
+            frequency,
+            clarity,
+            midi_note: midi_note as u8,
+            cents: (midi - midi_note) * 100.0,
+        }
+    }
+}
+
+/// Fractional MIDI note number of `hz`.
+pub fn frequency_to_midi(hz: f32) -> f32 {
+    69.0 + 12.0 * (hz / 440.0).log2()
+}
+
+/// Frequency in Hz of the fractional MIDI note number `midi`.
+pub fn midi_to_frequency(midi: f32) -> f32 {
+    440.0 * 2f32.powf((midi - 69.0) / 12.0)
+}
+
+/// YIN fundamental frequency estimator (de Cheveigné & Kawahara).
+///
+/// Looks for the first lag whose cumulative mean normalized difference dips below
+/// `threshold`. Periods up to half the frame length can be detected.
+#[derive(Debug, Clone)]
+pub struct Yin {
+    sample_rate: f32,
+    f_min: f32,
+    f_max: f32,
+    threshold: f32,
+    /// Cumulative mean normalized difference for each lag
+    difference: Vec<f32>,
+}
+
+impl Yin {
+    /// Creates a detector for 40 Hz to 2 kHz with a threshold of 0.15.
+    ///
+    /// # Panics
+    ///
+    /// If `sample_rate` is not finite and positive.
+    pub fn new(sample_rate: f32) -> Self {
+        if !(sample_rate.is_finite() && sample_rate > 0.0) {
+            panic!("sample rate ({}) must be finite and positive", sample_rate)
+        }
+        Yin {
+            sample_rate,
+            f_min: 40.0,
+            f_max: 2000.0,
+            threshold: 0.15,
+            difference: Vec::new(),
+        }
+    }
+
+    /// Limits the detected frequencies to `f_min..=f_max`.
+    ///
+    /// Frames must hold at least two periods of `f_min`.
+    ///
+    /// # Panics
+    ///
+    /// If `f_min` is not positive or is greater than `f_max`.
+    pub fn with_range(mut self, f_min: f32, f_max: f32) -> Self {
+        if f_min <= 0.0 || f_min > f_max {
+            panic!("invalid frequency range {}..={}", f_min, f_max)
+        }
+        self.f_min = f_min;
+        self.f_max = f_max;
+        self
+    }
+
+    /// Lower values reject more noisy frames, typical values are 0.1 to 0.2.
+    ///
+    /// # Panics
+    ///
+    /// If `threshold` is not finite and positive.
+    pub fn with_threshold(mut self, threshold: f32) -> Self {
+        if !(threshold.is_finite() && threshold > 0.0) {
+            panic!("threshold ({}) must be finite and positive", threshold)
+        }
+        self.threshold = threshold;
+        self
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    /// Estimates the pitch of a mono `frame`, `None` if it is silent or not periodic enough.
+    pub fn detect(&mut self, frame: &[f32]) -> Option<Pitch> {
+        let window = frame.len() / 2;
+        let energy = frame.iter().map(|s| s * s).sum::<f32>() / frame.len().max(1) as f32;
+        if energy < SILENCE {
+            return None;
+        }
+        let tau_min = ((self.sample_rate / self.f_max).floor() as usize).max(2);
+        let tau_max =
+            ((self.sample_rate / self.f_min).ceil() as usize).min(window.saturating_sub(1));
+        if tau_min >= tau_max {
+            return None;
+        }
+
+        self.difference.clear();
+        self.difference.resize(tau_max + 2, 1.0);
+        let mut running_sum = 0.0;
+        for tau in 1..tau_max + 2 {
+            let d: f32 = frame[..window]
+                .iter()
+                .zip(frame[tau..tau + window].iter())
+                .map(|(a, b)| (a - b) * (a - b))
+                .sum();
+            running_sum += d;
+            self.difference[tau] = if running_sum > 0.0 {
+                d * tau as f32 / running_sum
+            } else {
+                1.0
+            };
+        }
+
+        let d = &self.difference;
+        let mut tau = (tau_min..=tau_max).find(|&tau| d[tau] < self.threshold)?;
+        while tau < tau_max && d[tau + 1] < d[tau] {
+            tau += 1;
+        }
+
+        // Parabolic interpolation of the dip
+        let (a, b, c) = (d[tau - 1], d[tau], d[tau + 1]);
+        let denominator = a - 2.0 * b + c;
+        let offset = if denominator > 0.0 {
+            (0.5 * (a - c) / denominator).clamp(-0.5, 0.5)
+        } else {
+            0.0
+        };
+        let minimum = b - 0.25 * (a - c) * offset;
+        let period = tau as f32 + offset;
+        Some(Pitch::new(
+            self.sample_rate / period,
+            (1.0 - minimum).clamp(0.0, 1.0),
+        ))
+    }
+}
+
+/// Monophonic pitch tracker over an interleaved sample stream.
+///
+/// Keeps the last `IB_LEN` interleaved samples, mixes them to mono and runs [`Yin`]
+/// every `hop` samples.
+pub struct PitchConsumer<const IB_LEN: usize, T: Consumer<Item = f32>> {
+    consumer: T,
+    /// Circular history of the last input samples, see [`Framer::history`]
+    pub samples: [f32; IB_LEN],
+    /// Last estimate, `None` while the input is silent or unvoiced
+    pub pitch: Option<Pitch>,
+    framer: Framer,
+    channels: u16,
+    /// Mono mix of the history in chronological order
+    frame: Vec<f32>,
+    frames: u64,
+    detector: Yin,
+}
+
+pub type AudioConsumerPitchF32<const IB_LEN: usize> = PitchConsumer<IB_LEN, HeapConsumerF32>;
+
+impl<const IB_LEN: usize, T: Consumer<Item = f32>> PitchConsumer<IB_LEN, T> {
+    /// Creates a tracker without overlap, a new estimate is computed every `IB_LEN` samples.
+    ///
+    /// # Panics
+    ///
+    /// If `IB_LEN` is not a multiple of the number of channels, or `sample_rate` is not
+    /// finite and positive.
+    pub fn new(consumer: T, sample_rate: f32, channels: u16) -> Self {
+        if channels == 0 || !IB_LEN.is_multiple_of(channels as usize) {
+            panic!(
+                "buffer len ({}) must be a multiple of channels ({})",
+                IB_LEN, channels
+            )
+        }
+        PitchConsumer {
+            consumer,
+            samples: [0.0; IB_LEN],
+            pitch: None,
+            framer: Framer::new(IB_LEN, IB_LEN),
+            channels,
+            frame: vec![0.0; IB_LEN / channels as usize],
+            frames: 0,
+            detector: Yin::new(sample_rate),
+        }
+    }
+
+    /// Computes a new estimate every `hop` interleaved samples.
+    ///
+    /// # Panics
+    ///
+    /// If `hop` is 0, greater than `IB_LEN` or not a multiple of the number of channels.
+    pub fn with_hop(mut self, hop: usize) -> Self {
+        if hop == 0 || hop > IB_LEN || !hop.is_multiple_of(self.channels as usize) {
+            panic!(
+                "hop ({}) must be in 1..={} and a multiple of channels ({})",
+                hop, IB_LEN, self.channels
+            )
+        }
+        self.framer.set_hop(hop);
+        self
+    }
+
+    /// Replaces the detector, e.g. to change its range or threshold.
+    pub fn with_detector(mut self, detector: Yin) -> Self {
+        self.detector = detector;
+        self
+    }
+
+    pub fn hop(&self) -> usize {
+        self.framer.hop()
+    }
+
+    /// Number of estimates computed since creation.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    fn process_frame(&mut self) {
+        let channels = self.channels as usize;
+        let interleaved = self.framer.frame().chunks_exact(channels);
+        for (mono, frame) in self.frame.iter_mut().zip(interleaved) {
+            *mono = frame.iter().sum::<f32>() / channels as f32;
+        }
+        self.pitch = self.detector.detect(&self.frame);
+        self.frames += 1;
+    }
+
+    /// Reads every available sample and updates `pitch` each time `hop` new samples arrived.
+    ///
+    /// Takes the time since the previous call like the other consumers, the estimate
+    /// itself only depends on the samples.
+    pub fn update(&mut self, _elapsed: Duration) {
+        while let Some(sample) = self.consumer.try_pop() {
+            if self.framer.push(sample) {
+                self.process_frame();
+            }
+        }
+        self.samples.copy_from_slice(self.framer.history());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ringbuf::{traits::*, HeapRb};
+
+    use super::*;
+    use crate::test_utils::{noise, tones};
+
+    const SAMPLE_RATE: f32 = 44100.0;
+
+    fn harmonics(hz: f32, amplitudes: &[f32], len: usize) -> Vec<f32> {
+        let partials: Vec<(f32, f32)> = amplitudes
+            .iter()
+            .enumerate()
+            .map(|(k, &a)| (hz * (k + 1) as f32, a))
+            .collect();
+        tones(&partials, SAMPLE_RATE, len)
+    }
+
+    #[test]
+    fn detects_a_sine() {
+        let mut yin = Yin::new(SAMPLE_RATE);
+        for hz in [82.4, 220.0, 261.63, 1000.0] {
+            let pitch = yin.detect(&harmonics(hz, &[0.5], 2048)).unwrap();
+            assert!(
+                (pitch.frequency - hz).abs() / hz < 0.002,
+                "{} != {}",
+                pitch.frequency,
+                hz
+            );
+            assert!(pitch.clarity > 0.95);
+        }
+    }
+
+    #[test]
+    fn finds_the_fundamental_of_a_harmonic_tone() {
+        let mut yin = Yin::new(SAMPLE_RATE);
+        // The second harmonic is louder than the fundamental
+        let samples = harmonics(110.0, &[0.3, 0.5, 0.2, 0.1], 2048);
+        let pitch = yin.detect(&samples).unwrap();
+        assert!((pitch.frequency - 110.0).abs() < 0.5, "{}", pitch.frequency);
+        assert_eq!(pitch.midi_note, 45);
+    }
+
+    #[test]
+    fn noise_and_silence_are_unvoiced() {
+        let mut yin = Yin::new(SAMPLE_RATE);
+        assert_eq!(yin.detect(&[0.0; 2048]), None);
+        assert_eq!(yin.detect(&noise(2048, 7)), None);
+    }
+
+    #[test]
+    fn nearest_midi_note_and_cents() {
+        let a4 = Pitch::new(440.0, 1.0);
+        assert_eq!(a4.midi_note, 69);
+        assert!(a4.cents.abs() < 1e-3);
+
+        let sharp = Pitch::new(445.0, 1.0);
+        assert_eq!(sharp.midi_note, 69);
+        assert!((sharp.cents - 19.56).abs() < 0.01, "{}", sharp.cents);
+
+        let flat = Pitch::new(midi_to_frequency(60.0 - 0.3), 1.0);
+        assert_eq!(flat.midi_note, 60);
+        assert!((flat.cents + 30.0).abs() < 0.01, "{}", flat.cents);
+    }
+
+    #[test]
+    fn consumer_tracks_interleaved_stereo() {
+        let (mut prod, cons) = HeapRb::<f32>::new(8192).split();
+        let mut consumer = PitchConsumer::<4096, _>::new(cons, SAMPLE_RATE, 2).with_hop(1024);
+        let mono = harmonics(196.0, &[0.5, 0.25], 3072);
+        let stereo: Vec<f32> = mono.iter().flat_map(|&s| [s, s]).collect();
+        for chunk in stereo.chunks(500) {
+            prod.push_slice(chunk);
+            consumer.update(Duration::from_millis(10));
+        }
+        // First estimate once 4096 samples arrived, then one every 1024
+        assert_eq!(consumer.frames(), 3);
+        let pitch = consumer.pitch.unwrap();
+        assert!((pitch.frequency - 196.0).abs() < 0.5, "{}", pitch.frequency);
+        assert_eq!(pitch.midi_note, 55);
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_a_zero_sample_rate() {
+        let _ = Yin::new(0.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_a_nan_threshold() {
+        let _ = Yin::new(SAMPLE_RATE).with_threshold(f32::NAN);
+    }
+}
@@ -0,0 +1,1270 @@
+use std::{fmt, time::Duration};
+
+use fft_analizer::{
+    chord::{Chord, ChordRecognizer},
+    chroma::Chromagram,
+    descriptors::{Descriptors, SpectralDescriptors},
+    key::{Key, KeyDetector},
+    window::WindowFunction,
+    Framer, FrequencySpectrum, Normalization, NormalizationError, Normalizer, Scale,
+    DEFAULT_SAMPLE_RATE,
+};
+use ringbuf::traits::Consumer;
+
+use crate::{
+    bandpass::Bandpass,
+    beat::{Beat, BeatTracker},
+    onset::{Onset, OnsetDetector},
+    smoothing::{Smoother, SmoothingError, SmoothingMode},
+    Analyzer, HeapConsumerF32,
+};
+
+/// Invalid setting rejected by [`FftConfig::validate`] or [`FilterBankConfig::validate`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigError {
+    /// No input channel
+    NoChannels,
+    /// The frame is empty, or for the FFT shorter than two samples per channel
+    /// or not a multiple of the number of channels
+    FrameLen { frame_len: usize, channels: u16 },
+    /// No band, or more bands than the analyzer produces
+    Bands { bands: usize, max: usize },
+    /// The hop is 0, longer than the frame or not a multiple of the number of channels
+    Hop { hop: usize, frame_len: usize },
+    /// The FFT is shorter than the frame of one channel
+    FftSize { fft_size: usize, window_len: usize },
+    /// The sample rate is not positive
+    SampleRate(f32),
+    /// The band range is empty or reaches the Nyquist frequency
+    FrequencyRange { f_min: f32, f_max: f32 },
+    /// A smoothing time constant is negative, infinite or NaN
+    Smoothing(SmoothingError),
+    /// A normalization parameter is out of range or the normalization doesn't suit the scale
+    Normalization(NormalizationError),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::NoChannels => write!(f, "input must have at least one channel"),
+            ConfigError::FrameLen {
+                frame_len,
+                channels,
+            } => write!(
+                f,
+                "frame length ({}) must hold at least 2 samples for each of the {} channels",
+                frame_len, channels
+            ),
+            ConfigError::Bands { bands, max } => {
+                write!(f, "number of bands ({}) must be in 1..={}", bands, max)
+            }
+            ConfigError::Hop { hop, frame_len } => write!(
+                f,
+                "hop ({}) must be in 1..={} and a multiple of channels",
+                hop, frame_len
+            ),
+            ConfigError::FftSize {
+                fft_size,
+                window_len,
+            } => write!(
+                f,
+                "fft size ({}) must be at least the window length ({})",
+                fft_size, window_len
+            ),
+            ConfigError::SampleRate(sample_rate) => {
+                write!(f, "sample rate ({}) must be positive", sample_rate)
+            }
+            ConfigError::FrequencyRange { f_min, f_max } => write!(
+                f,
+                "band range {}..={} Hz must be positive and below Nyquist",
+                f_min, f_max
+            ),
+            ConfigError::Smoothing(error) => error.fmt(f),
+            ConfigError::Normalization(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<SmoothingError> for ConfigError {
+    fn from(error: SmoothingError) -> Self {
+        ConfigError::Smoothing(error)
+    }
+}
+
+impl From<NormalizationError> for ConfigError {
+    fn from(error: NormalizationError) -> Self {
+        ConfigError::Normalization(error)
+    }
+}
+
+fn smoother(smoothing: (f32, f32), mode: SmoothingMode) -> Result<Smoother, ConfigError> {
+    let (attack, release) = smoothing;
+    Ok(Smoother::try_new(attack, release)?.with_mode(mode))
+}
+
+fn validate_sample_rate(sample_rate: f32) -> Result<(), ConfigError> {
+    if !(sample_rate > 0.0 && sample_rate.is_finite()) {
+        return Err(ConfigError::SampleRate(sample_rate));
+    }
+    Ok(())
+}
+
+/// Settings of a [`RuntimeFftConsumer`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FftConfig {
+    frame_len: usize,
+    channels: u16,
+    /// `None` keeps every bin of the spectrum
+    bands: Option<usize>,
+    /// `None` doesn't overlap frames
+    hop: Option<usize>,
+    sample_rate: f32,
+    window: WindowFunction,
+    /// `None` doesn't zero-pad the frames
+    fft_size: Option<usize>,
+    scale: Scale,
+    /// `None` follows the scale, see [`FrequencySpectrum::with_scale`]
+    normalization: Option<Normalization>,
+    /// Attack and release time constants in seconds
+    smoothing: (f32, f32),
+    smoothing_mode: SmoothingMode,
+}
+
+impl FftConfig {
+    /// Frames of `frame_len` interleaved samples, without overlap, keeping every bin.
+    pub fn new(frame_len: usize, channels: u16) -> Self {
+        FftConfig {
+            frame_len,
+            channels,
+            bands: None,
+            hop: None,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            window: WindowFunction::default(),
+            fft_size: None,
+            scale: Scale::default(),
+            normalization: None,
+            smoothing: (0.25, 0.25),
+            smoothing_mode: SmoothingMode::default(),
+        }
+    }
+
+    /// Keeps the first `bands` bins of the spectrum.
+    pub fn with_bands(mut self, bands: usize) -> Self {
+        self.bands = Some(bands);
+        self
+    }
+
+    /// Computes a new spectrum every `hop` interleaved samples.
+    pub fn with_hop(mut self, hop: usize) -> Self {
+        self.hop = Some(hop);
+        self
+    }
+
+    pub fn with_sample_rate(mut self, sample_rate: f32) -> Self {
+        self.sample_rate = sample_rate;
+        self
+    }
+
+    /// Applies `window` before the FFT, Hann by default.
+    pub fn with_window(mut self, window: WindowFunction) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Zero-pads the frame of each channel up to `fft_size` samples, see
+    /// [`FrequencySpectrum::with_fft_size`].
+    pub fn with_fft_size(mut self, fft_size: usize) -> Self {
+        self.fft_size = Some(fft_size);
+        self
+    }
+
+    /// Scale of the bands, linear by default.
+    pub fn with_scale(mut self, scale: Scale) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Normalization of the bands, by default min-max or none on the dBFS scale.
+    pub fn with_normalization(mut self, normalization: Normalization) -> Self {
+        self.normalization = Some(normalization);
+        self
+    }
+
+    /// Smooths the bands with `attack` and `release` time constants in seconds,
+    /// 250 ms both ways by default. See [`Smoother`].
+    pub fn with_smoothing(mut self, attack: f32, release: f32) -> Self {
+        self.smoothing = (attack, release);
+        self
+    }
+
+    pub fn with_smoothing_mode(mut self, mode: SmoothingMode) -> Self {
+        self.smoothing_mode = mode;
+        self
+    }
+
+    pub fn frame_len(&self) -> usize {
+        self.frame_len
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    /// Number of bins in a spectrum of `fft_size` samples.
+    pub fn max_bands(&self) -> usize {
+        self.fft_size() / 2
+    }
+
+    pub fn bands(&self) -> usize {
+        self.bands.unwrap_or(self.max_bands())
+    }
+
+    pub fn hop(&self) -> usize {
+        self.hop.unwrap_or(self.frame_len)
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    pub fn window(&self) -> WindowFunction {
+        self.window
+    }
+
+    /// FFT length, the frame length of one channel unless zero-padded.
+    pub fn fft_size(&self) -> usize {
+        self.fft_size
+            .unwrap_or(self.frame_len / self.channels.max(1) as usize)
+    }
+
+    pub fn scale(&self) -> Scale {
+        self.scale
+    }
+
+    /// Normalization set with [`FftConfig::with_normalization`], `None` follows the scale.
+    pub fn normalization(&self) -> Option<Normalization> {
+        self.normalization
+    }
+
+    /// Attack and release time constants in seconds.
+    pub fn smoothing(&self) -> (f32, f32) {
+        self.smoothing
+    }
+
+    pub fn smoothing_mode(&self) -> SmoothingMode {
+        self.smoothing_mode
+    }
+
+    /// Number of frames computed per second of input.
+    pub fn frame_rate(&self) -> f32 {
+        self.sample_rate * self.channels as f32 / self.hop() as f32
+    }
+
+    /// Checks that a consumer can be built with these settings.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.channels == 0 {
+            return Err(ConfigError::NoChannels);
+        }
+        let channels = self.channels as usize;
+        if self.frame_len < 2 * channels || !self.frame_len.is_multiple_of(channels) {
+            return Err(ConfigError::FrameLen {
+                frame_len: self.frame_len,
+                channels: self.channels,
+            });
+        }
+        let (fft_size, window_len) = (self.fft_size(), self.frame_len / channels);
+        if fft_size < window_len {
+            return Err(ConfigError::FftSize {
+                fft_size,
+                window_len,
+            });
+        }
+        let (bands, max) = (self.bands(), self.max_bands());
+        if bands == 0 || bands > max {
+            return Err(ConfigError::Bands { bands, max });
+        }
+        let hop = self.hop();
+        if hop == 0 || hop > self.frame_len || !hop.is_multiple_of(channels) {
+            return Err(ConfigError::Hop {
+                hop,
+                frame_len: self.frame_len,
+            });
+        }
+        validate_sample_rate(self.sample_rate)?;
+        if let Some(normalization) = self.normalization {
+            normalization.validate(self.scale)?;
+        }
+        self.smoother()?;
+        Ok(())
+    }
+
+    /// Builds a consumer reading from `consumer`.
+    pub fn build<T: Consumer<Item = f32>>(
+        self,
+        consumer: T,
+    ) -> Result<RuntimeFftConsumer<T>, ConfigError> {
+        self.validate()?;
+        let mut fft = RuntimeFftConsumer {
+            consumer,
+            frequencies: Vec::new(),
+            smoothed: Vec::new(),
+            descriptors: SpectralDescriptors::default(),
+            band_frequencies: Vec::new(),
+            ready: false,
+            smoother: self.smoother()?,
+            framer: Framer::new(self.frame_len, self.hop()),
+            frames: 0,
+            read: 0,
+            start: Duration::ZERO,
+            fs: self.spectrum(),
+            magnitudes: Vec::new(),
+            tracker: Descriptors::new(),
+            onset_detector: None,
+            report_onsets: false,
+            onsets: Vec::new(),
+            beat_tracker: None,
+            beats: Vec::new(),
+            config: self.clone(),
+        };
+        fft.apply(self, false);
+        Ok(fft)
+    }
+
+    fn smoother(&self) -> Result<Smoother, ConfigError> {
+        smoother(self.smoothing, self.smoothing_mode)
+    }
+
+    fn spectrum(&self) -> FrequencySpectrum {
+        let fs = FrequencySpectrum::new(self.frame_len, self.channels)
+            .with_sample_rate(self.sample_rate)
+            .with_frame_rate(self.frame_rate())
+            .with_window(self.window)
+            .with_fft_size(self.fft_size())
+            .with_scale(self.scale);
+        match self.normalization {
+            Some(normalization) => fs.with_normalization(normalization),
+            None => fs,
+        }
+    }
+
+    /// Settings the [`FrequencySpectrum`] is built from, besides the history ones.
+    fn spectrum_settings(&self) -> (WindowFunction, usize, Scale, Option<Normalization>, f32) {
+        (
+            self.window,
+            self.fft_size(),
+            self.scale,
+            self.normalization,
+            self.frame_rate(),
+        )
+    }
+}
+
+/// Sliding STFT over an interleaved sample stream, with sizes and smoothing chosen at runtime.
+///
+/// Keeps the last `frame_len` samples and computes a new spectrum every `hop` samples,
+/// so consecutive frames overlap by `frame_len - hop` samples. [`crate::FftConsumer`]
+/// wraps it with sizes fixed at compile time.
+pub struct RuntimeFftConsumer<T: Consumer<Item = f32>> {
+    consumer: T,
+    frequencies: Vec<f32>,
+    smoothed: Vec<f32>,
+    /// Descriptors of the last frame, computed from its linear magnitudes
+    descriptors: SpectralDescriptors,
+    band_frequencies: Vec<f32>,
+    /// Whether the last update computed a frame
+    ready: bool,
+    smoother: Smoother,
+    framer: Framer,
+    /// Number of frames computed since creation
+    frames: u64,
+    /// Number of samples read since the history last restarted
+    read: u64,
+    /// Input time at which the history last restarted
+    start: Duration,
+    fs: FrequencySpectrum,
+    /// Linear magnitudes of the last frame
+    magnitudes: Vec<f32>,
+    tracker: Descriptors,
+    onset_detector: Option<OnsetDetector>,
+    /// Whether onsets are kept for [`RuntimeFftConsumer::drain_onsets`]
+    report_onsets: bool,
+    /// Onsets detected since the last drain
+    onsets: Vec<Onset>,
+    beat_tracker: Option<BeatTracker>,
+    /// Beats emitted since the last drain
+    beats: Vec<Beat>,
+    config: FftConfig,
+}
+
+pub type AudioConsumerRuntimeF32 = RuntimeFftConsumer<HeapConsumerF32>;
+
+impl<T: Consumer<Item = f32>> RuntimeFftConsumer<T> {
+    /// Sets the percentage of energy used for the rolloff descriptor, 85 by default.
+    ///
+    /// # Panics
+    ///
+    /// If `percent` is not in `0.0..=100.0`.
+    pub fn with_rolloff(mut self, percent: f32) -> Self {
+        self.tracker = self.tracker.with_rolloff(percent);
+        self
+    }
+
+    /// Runs `detector` on every frame, see [`RuntimeFftConsumer::drain_onsets`].
+    pub fn with_onset_detector(mut self, detector: OnsetDetector) -> Self {
+        self.onset_detector = Some(detector);
+        self.report_onsets = true;
+        self
+    }
+
+    /// Tracks the beat of the onset detection function, see [`RuntimeFftConsumer::drain_beats`].
+    ///
+    /// `tracker` must be created with [`RuntimeFftConsumer::frame_rate`], it is rescaled
+    /// when a reconfiguration changes the frame rate. Uses the detector set with
+    /// [`RuntimeFftConsumer::with_onset_detector`], or a default one.
+    pub fn with_beat_tracker(mut self, tracker: BeatTracker) -> Self {
+        self.onset_detector
+            .get_or_insert_with(OnsetDetector::default);
+        self.beat_tracker = Some(tracker);
+        self
+    }
+
+    pub fn config(&self) -> &FftConfig {
+        &self.config
+    }
+
+    /// Applies new settings, keeping the ring buffer and its unread samples.
+    ///
+    /// The sample history restarts when the frame length, channels or sample rate change.
+    /// The spectrum is rebuilt, forgetting the normalization level, when the window, FFT size,
+    /// scale, normalization or frame rate change. Invalid settings leave the consumer unchanged.
+    pub fn reconfigure(&mut self, config: FftConfig) -> Result<(), ConfigError> {
+        config.validate()?;
+        let restart = config.frame_len != self.config.frame_len
+            || config.channels != self.config.channels
+            || config.sample_rate != self.config.sample_rate;
+        self.apply(config, restart);
+        Ok(())
+    }
+
+    fn apply(&mut self, config: FftConfig, restart: bool) {
+        if restart {
+            self.start = self.time(self.read);
+            self.read = 0;
+            self.framer = Framer::new(config.frame_len, config.hop());
+        } else {
+            self.framer.set_hop(config.hop());
+        }
+        if restart || config.spectrum_settings() != self.config.spectrum_settings() {
+            self.fs = config.spectrum();
+            self.tracker = Descriptors::new().with_rolloff(self.tracker.rolloff_percent());
+        }
+        let frame_rate = config.frame_rate();
+        if let Some(tracker) = self.beat_tracker.as_mut() {
+            if tracker.frame_rate() != frame_rate {
+                *tracker = tracker.at_frame_rate(frame_rate);
+            }
+        }
+        if (config.smoothing, config.smoothing_mode)
+            != (self.config.smoothing, self.config.smoothing_mode)
+        {
+            self.smoother = config.smoother().expect("validated config");
+        }
+        let bands = config.bands();
+        self.frequencies.resize(bands, 0.0);
+        self.smoothed.resize(bands, 0.0);
+        self.band_frequencies.clear();
+        self.band_frequencies
+            .extend((0..bands).map(|i| self.fs.bin_frequency(i)));
+        self.config = config;
+    }
+
+    /// Circular history of the last input samples, see [`Framer::history`].
+    pub fn samples(&self) -> &[f32] {
+        self.framer.history()
+    }
+
+    /// Descriptors of the last frame, computed from its linear magnitudes.
+    pub fn descriptors(&self) -> &SpectralDescriptors {
+        &self.descriptors
+    }
+
+    /// Number of frames computed per second of input.
+    pub fn frame_rate(&self) -> f32 {
+        self.config.frame_rate()
+    }
+
+    /// Estimated tempo, 0 without a beat tracker or until it has seen enough input.
+    pub fn bpm(&self) -> f32 {
+        self.beat_tracker.as_ref().map_or(0.0, BeatTracker::bpm)
+    }
+
+    /// Position within the current beat, from 0 on the beat to 1 just before the next one.
+    pub fn beat_phase(&self) -> f32 {
+        self.beat_tracker
+            .as_ref()
+            .map_or(0.0, BeatTracker::beat_phase)
+    }
+
+    /// Removes and returns the onsets detected since the last call, oldest first.
+    ///
+    /// Onsets accumulate until drained, so call it after every [`RuntimeFftConsumer::update`].
+    pub fn drain_onsets(&mut self) -> std::vec::Drain<'_, Onset> {
+        self.onsets.drain(..)
+    }
+
+    /// Removes and returns the beats emitted since the last call, oldest first.
+    pub fn drain_beats(&mut self) -> std::vec::Drain<'_, Beat> {
+        self.beats.drain(..)
+    }
+
+    /// Number of spectra computed since creation.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Input time of the interleaved sample `read` samples after the last restart.
+    fn time(&self, read: u64) -> Duration {
+        let frames = read / self.config.channels as u64;
+        self.start + Duration::from_secs_f64(frames as f64 / self.config.sample_rate as f64)
+    }
+
+    fn process_frame(&mut self) {
+        let ff = self.fs.frequency_spectrum(self.framer.frame());
+        let bands = self.frequencies.len();
+        self.frequencies.copy_from_slice(&ff[..bands]);
+        self.magnitudes.clear();
+        self.magnitudes
+            .extend(self.fs.complex_bins(0).iter().map(|bin| bin.norm()));
+        self.descriptors = self.tracker.compute(&self.magnitudes, self.fs.axis());
+        // Onsets are timestamped at the centre of the frame
+        let time = self.time(self.read - self.config.frame_len as u64 / 2);
+        if let Some(detector) = self.onset_detector.as_mut() {
+            let onset = detector.process(self.fs.complex_bins(0), time);
+            if let Some(onset) = onset.filter(|_| self.report_onsets) {
+                self.onsets.push(onset);
+            }
+            if let Some(tracker) = self.beat_tracker.as_mut() {
+                self.beats.extend(tracker.push(detector.value(), time));
+            }
+        }
+        self.frames += 1;
+    }
+
+    /// Reads every available sample, computes a frame each time `hop` new samples arrived
+    /// and smooths the bands over `milis`.
+    pub fn update(&mut self, milis: Duration) {
+        self.ready = false;
+        while let Some(sample) = self.consumer.try_pop() {
+            self.read += 1;
+            if self.framer.push(sample) {
+                self.process_frame();
+                self.ready = true;
+            }
+        }
+        self.smoother
+            .smooth(&mut self.smoothed, &self.frequencies, milis);
+    }
+}
+
+impl<T: Consumer<Item = f32>> Analyzer for RuntimeFftConsumer<T> {
+    fn update(&mut self, elapsed: Duration) {
+        RuntimeFftConsumer::update(self, elapsed);
+    }
+
+    fn frame_ready(&self) -> bool {
+        self.ready
+    }
+
+    fn bands(&self) -> &[f32] {
+        &self.frequencies
+    }
+
+    fn smoothed(&self) -> &[f32] {
+        &self.smoothed
+    }
+
+    fn band_frequencies(&self) -> &[f32] {
+        &self.band_frequencies
+    }
+}
+
+/// Settings of a [`RuntimeFilterBankConsumer`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterBankConfig {
+    frame_len: usize,
+    bands: usize,
+    f_min: f32,
+    f_max: f32,
+    sample_rate: f32,
+    scale: Scale,
+    normalization: Normalization,
+    /// Attack and release time constants in seconds
+    smoothing: (f32, f32),
+    smoothing_mode: SmoothingMode,
+}
+
+impl FilterBankConfig {
+    /// Semitone spaced bands from `f_min` to `f_max` Hz, at most `bands` of them,
+    /// measured over frames of `frame_len` samples.
+    pub fn new(frame_len: usize, bands: usize, f_min: f32, f_max: f32) -> Self {
+        FilterBankConfig {
+            frame_len,
+            bands,
+            f_min,
+            f_max,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            scale: Scale::default(),
+            normalization: Normalization::None,
+            smoothing: (0.25, 0.25),
+            smoothing_mode: SmoothingMode::default(),
+        }
+    }
+
+    pub fn with_sample_rate(mut self, sample_rate: f32) -> Self {
+        self.sample_rate = sample_rate;
+        self
+    }
+
+    /// Scale of the band levels, linear RMS by default. A full-scale sine at a band centre
+    /// reads 0 dBFS.
+    pub fn with_scale(mut self, scale: Scale) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Normalization of the band levels, none by default. Chroma, key and chord are
+    /// computed before scaling and normalization.
+    pub fn with_normalization(mut self, normalization: Normalization) -> Self {
+        self.normalization = normalization;
+        self
+    }
+
+    /// Smooths the bands with `attack` and `release` time constants in seconds,
+    /// 250 ms both ways by default. See [`Smoother`].
+    pub fn with_smoothing(mut self, attack: f32, release: f32) -> Self {
+        self.smoothing = (attack, release);
+        self
+    }
+
+    pub fn with_smoothing_mode(mut self, mode: SmoothingMode) -> Self {
+        self.smoothing_mode = mode;
+        self
+    }
+
+    pub fn frame_len(&self) -> usize {
+        self.frame_len
+    }
+
+    /// Number of frames computed per second of input.
+    pub fn frame_rate(&self) -> f32 {
+        self.sample_rate / self.frame_len as f32
+    }
+
+    /// Maximum number of bands, fewer fit between `f_min` and `f_max` when the range is narrow.
+    pub fn bands(&self) -> usize {
+        self.bands
+    }
+
+    pub fn frequency_range(&self) -> (f32, f32) {
+        (self.f_min, self.f_max)
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    pub fn scale(&self) -> Scale {
+        self.scale
+    }
+
+    pub fn normalization(&self) -> Normalization {
+        self.normalization
+    }
+
+    /// Attack and release time constants in seconds.
+    pub fn smoothing(&self) -> (f32, f32) {
+        self.smoothing
+    }
+
+    pub fn smoothing_mode(&self) -> SmoothingMode {
+        self.smoothing_mode
+    }
+
+    /// Checks that a consumer can be built with these settings.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.frame_len == 0 {
+            return Err(ConfigError::FrameLen {
+                frame_len: self.frame_len,
+                channels: 1,
+            });
+        }
+        if self.bands == 0 {
+            return Err(ConfigError::Bands {
+                bands: self.bands,
+                max: usize::MAX,
+            });
+        }
+        validate_sample_rate(self.sample_rate)?;
+        if !(self.f_min > 0.0 && self.f_min <= self.f_max && self.f_max < self.sample_rate / 2.0) {
+            return Err(ConfigError::FrequencyRange {
+                f_min: self.f_min,
+                f_max: self.f_max,
+            });
+        }
+        self.normalization.validate(self.scale)?;
+        self.smoother()?;
+        Ok(())
+    }
+
+    /// Builds a consumer reading from `consumer`.
+    pub fn build<T: Consumer<Item = f32>>(
+        self,
+        consumer: T,
+    ) -> Result<RuntimeFilterBankConsumer<T>, ConfigError> {
+        self.validate()?;
+        let mut filter_bank = RuntimeFilterBankConsumer {
+            consumer,
+            framer: Framer::new(self.frame_len, self.frame_len),
+            frequencies: Vec::new(),
+            smoothed: Vec::new(),
+            filters: Vec::new(),
+            centers: Vec::new(),
+            chroma: [0.0; 12],
+            key: None,
+            chord: None,
+            chromagram: Chromagram::new().with_tuning(0.0),
+            key_detector: KeyDetector::new(32),
+            chord_recognizer: ChordRecognizer::new().with_smoothing(0.9),
+            ready: false,
+            normalizer: Normalizer::new(self.normalization),
+            smoother: self.smoother()?,
+            config: self.clone(),
+        };
+        filter_bank.apply(self);
+        Ok(filter_bank)
+    }
+
+    fn smoother(&self) -> Result<Smoother, ConfigError> {
+        smoother(self.smoothing, self.smoothing_mode)
+    }
+}
+
+/// Bank of semitone spaced bandpass filters over a mono sample stream, with sizes and
+/// smoothing chosen at runtime.
+///
+/// Measures the RMS level of each band over consecutive frames and folds them into
+/// chroma, key and chord estimates. [`crate::bandpass::FilterBankConsumer`] wraps it
+/// with sizes fixed at compile time.
+pub struct RuntimeFilterBankConsumer<T: Consumer<Item = f32>> {
+    consumer: T,
+    framer: Framer,
+    frequencies: Vec<f32>,
+    smoothed: Vec<f32>,
+    filters: Vec<Bandpass>,
+    /// Centre frequency of each filter
+    centers: Vec<f32>,
+    /// Band energy folded into pitch classes starting at C, see [`Chromagram`]
+    chroma: [f32; 12],
+    key: Option<Key>,
+    chord: Option<Chord>,
+    chromagram: Chromagram,
+    key_detector: KeyDetector,
+    chord_recognizer: ChordRecognizer,
+    /// Whether the last update computed a frame
+    ready: bool,
+    normalizer: Normalizer,
+    smoother: Smoother,
+    config: FilterBankConfig,
+}
+
+pub type AudioConsumerRuntimeFilterBankF32 = RuntimeFilterBankConsumer<HeapConsumerF32>;
+
+impl<T: Consumer<Item = f32>> RuntimeFilterBankConsumer<T> {
+    /// Detects the key over the last `frames` frames, 32 by default.
+    ///
+    /// # Panics
+    ///
+    /// If `frames` is 0.
+    pub fn with_key_window(mut self, frames: usize) -> Self {
+        self.key_detector = KeyDetector::new(frames);
+        self
+    }
+
+    /// Replaces the chord recognizer, smoothed with a self transition probability of 0.9 by default.
+    pub fn with_chord_recognizer(mut self, recognizer: ChordRecognizer) -> Self {
+        self.chord_recognizer = recognizer;
+        self
+    }
+
+    /// Replaces the chromagram, e.g. to change the reference or the normalization.
+    ///
+    /// The default chromagram keeps the tuning at 0 cents: with Q = 200 filters a semitone
+    /// apart, a peak between two bands barely reaches either of them, so the tuning
+    /// estimated from the band energies is unreliable.
+    pub fn with_chromagram(mut self, chromagram: Chromagram) -> Self {
+        self.chromagram = chromagram;
+        self
+    }
+
+    pub fn config(&self) -> &FilterBankConfig {
+        &self.config
+    }
+
+    /// Applies new settings, keeping the ring buffer and its unread samples.
+    ///
+    /// Only scale, normalization and smoothing changes keep the filter states and the
+    /// partial frame. Invalid settings leave the consumer unchanged.
+    pub fn reconfigure(&mut self, config: FilterBankConfig) -> Result<(), ConfigError> {
+        config.validate()?;
+        if (config.smoothing, config.smoothing_mode)
+            != (self.config.smoothing, self.config.smoothing_mode)
+        {
+            self.smoother = config.smoother()?;
+        }
+        if config.normalization != self.config.normalization {
+            self.normalizer = Normalizer::new(config.normalization);
+        }
+        let same_bank = FilterBankConfig {
+            scale: self.config.scale,
+            normalization: self.config.normalization,
+            smoothing: self.config.smoothing,
+            smoothing_mode: self.config.smoothing_mode,
+            ..config.clone()
+        } == self.config;
+        if same_bank {
+            self.config = config;
+        } else {
+            self.apply(config);
+        }
+        Ok(())
+    }
+
+    fn apply(&mut self, config: FilterBankConfig) {
+        self.filters.clear();
+        self.centers.clear();
+        let q = 200.0; // quality factor (adjust for bandwidth)
+        let mut f = config.f_min;
+        while f <= config.f_max && self.filters.len() < config.bands {
+            self.filters.push(Bandpass::new(f, q, config.sample_rate));
+            self.centers.push(f);
+            f *= 2f32.powf(1.0 / 12.0); // semitone steps
+        }
+        self.frequencies = vec![0.0; self.filters.len()];
+        self.smoothed.resize(self.filters.len(), 0.0);
+        self.framer = Framer::new(config.frame_len, config.frame_len);
+        self.normalizer = Normalizer::new(config.normalization);
+        self.config = config;
+    }
+
+    /// Samples of the current frame, see [`Framer::history`].
+    pub fn samples(&self) -> &[f32] {
+        self.framer.history()
+    }
+
+    /// Band energy folded into pitch classes starting at C, see [`Chromagram`].
+    pub fn chroma(&self) -> &[f32; 12] {
+        &self.chroma
+    }
+
+    /// Key of the chroma of the last frames.
+    pub fn key(&self) -> Option<Key> {
+        self.key
+    }
+
+    /// Chord of the last frame, `None` when no chord matches.
+    pub fn chord(&self) -> Option<Chord> {
+        self.chord
+    }
+
+    /// Tuning of the chromagram in cents, 0 unless one estimating it was set with `with_chromagram`.
+    pub fn tuning(&self) -> f32 {
+        self.chromagram.tuning()
+    }
+
+    fn process_frame(&mut self) {
+        let frame = self.framer.frame();
+        for (filter, band) in self.filters.iter_mut().zip(self.frequencies.iter_mut()) {
+            let mut energy = 0.0;
+            for &s in frame {
+                let y = filter.process(s);
+                energy += y * y;
+            }
+            *band = (energy / frame.len() as f32).sqrt(); // RMS energy
+        }
+        self.chroma = *self
+            .chromagram
+            .process_bands(&self.frequencies, &self.centers);
+        self.key = self.key_detector.push(&self.chroma);
+        self.chord = self.chord_recognizer.push(&self.chroma);
+        if self.config.scale != Scale::Linear {
+            // The RMS of a full-scale sine
+            let full_scale = std::f32::consts::FRAC_1_SQRT_2;
+            for band in self.frequencies.iter_mut() {
+                *band = self.config.scale.convert(*band, full_scale);
+            }
+        }
+        self.normalizer
+            .apply(&mut self.frequencies, self.config.frame_rate());
+    }
+
+    /// Reads every available sample, computes a frame each time `frame_len` samples arrived
+    /// and smooths the bands over `milis`.
+    pub fn update(&mut self, milis: Duration) {
+        self.ready = false;
+        while let Some(sample) = self.consumer.try_pop() {
+            if self.framer.push(sample) {
+                self.process_frame();
+                self.ready = true;
+            }
+        }
+        self.smoother
+            .smooth(&mut self.smoothed, &self.frequencies, milis);
+    }
+}
+
+impl<T: Consumer<Item = f32>> Analyzer for RuntimeFilterBankConsumer<T> {
+    fn update(&mut self, elapsed: Duration) {
+        RuntimeFilterBankConsumer::update(self, elapsed);
+    }
+
+    fn frame_ready(&self) -> bool {
+        self.ready
+    }
+
+    /// RMS level of each band, bands above `f_max` are left out.
+    fn bands(&self) -> &[f32] {
+        &self.frequencies
+    }
+
+    fn smoothed(&self) -> &[f32] {
+        &self.smoothed
+    }
+
+    fn band_frequencies(&self) -> &[f32] {
+        &self.centers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ringbuf::{traits::*, HeapRb};
+
+    use super::*;
+    use crate::{
+        bandpass::FilterBankConsumer,
+        test_utils::{ramp, sine},
+        FftConsumer,
+    };
+
+    #[test]
+    fn matches_the_const_generic_consumer() {
+        let samples = ramp(200);
+        let (mut prod, cons) = HeapRb::<f32>::new(256).split();
+        let mut fixed = FftConsumer::<64, 16, 4, _>::new(cons, 2)
+            .with_sample_rate(8000.0)
+            .with_hop(16);
+        prod.push_slice(&samples);
+        fixed.update(Duration::from_millis(100));
+
+        let (mut prod, cons) = HeapRb::<f32>::new(256).split();
+        let mut runtime = FftConfig::new(64, 2)
+            .with_bands(16)
+            .with_hop(16)
+            .with_sample_rate(8000.0)
+            .with_smoothing(0.25, 0.25)
+            .build(cons)
+            .unwrap();
+        prod.push_slice(&samples);
+        runtime.update(Duration::from_millis(100));
+
+        assert_eq!(runtime.frames(), fixed.frames());
+        assert_eq!(runtime.frame_rate(), fixed.frame_rate());
+        assert_eq!(Analyzer::bands(&runtime), &fixed.frequencies[..]);
+        assert_eq!(Analyzer::smoothed(&runtime), &fixed.smoothed[..]);
+        assert_eq!(
+            Analyzer::band_frequencies(&runtime),
+            Analyzer::band_frequencies(&fixed)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_settings() {
+        let cases = [
+            (FftConfig::new(64, 0), ConfigError::NoChannels),
+            (
+                FftConfig::new(63, 2),
+                ConfigError::FrameLen {
+                    frame_len: 63,
+                    channels: 2,
+                },
+            ),
+            (
+                FftConfig::new(64, 1).with_bands(33),
+                ConfigError::Bands { bands: 33, max: 32 },
+            ),
+            (
+                FftConfig::new(64, 2).with_hop(15),
+                ConfigError::Hop {
+                    hop: 15,
+                    frame_len: 64,
+                },
+            ),
+            (
+                FftConfig::new(64, 1).with_sample_rate(0.0),
+                ConfigError::SampleRate(0.0),
+            ),
+            (
+                FftConfig::new(64, 2).with_fft_size(16),
+                ConfigError::FftSize {
+                    fft_size: 16,
+                    window_len: 32,
+                },
+            ),
+            (
+                FftConfig::new(64, 1)
+                    .with_scale(Scale::Dbfs { floor: -120.0 })
+                    .with_normalization(Normalization::RunningPeak { decay: 0.9 }),
+                ConfigError::Normalization(NormalizationError::Scale(
+                    Normalization::RunningPeak { decay: 0.9 },
+                    Scale::Dbfs { floor: -120.0 },
+                )),
+            ),
+        ];
+        for (config, error) in cases {
+            assert_eq!(config.validate(), Err(error));
+        }
+        let config = FilterBankConfig::new(256, 88, 27.5, 4186.0).with_sample_rate(8000.0);
+        assert_eq!(
+            config.validate(),
+            Err(ConfigError::FrequencyRange {
+                f_min: 27.5,
+                f_max: 4186.0
+            })
+        );
+        let config = FilterBankConfig::new(256, 12, 440.0, 880.0)
+            .with_sample_rate(8000.0)
+            .with_normalization(Normalization::Fixed { reference: -1.0 });
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::Normalization(NormalizationError::Parameter(_)))
+        ));
+    }
+
+    #[test]
+    fn spectrum_settings_match_frequency_spectrum() {
+        let samples = sine(1000.0, 8000.0, 256);
+        let mut fs = FrequencySpectrum::new(256, 1)
+            .with_sample_rate(8000.0)
+            .with_window(WindowFunction::FlatTop)
+            .with_fft_size(1024)
+            .with_scale(Scale::Dbfs { floor: -100.0 });
+        let expected = fs.frequency_spectrum(&samples).to_vec();
+
+        let (mut prod, cons) = HeapRb::<f32>::new(1024).split();
+        let config = FftConfig::new(256, 1)
+            .with_sample_rate(8000.0)
+            .with_window(WindowFunction::FlatTop)
+            .with_fft_size(1024)
+            .with_scale(Scale::Dbfs { floor: -100.0 });
+        assert_eq!(config.max_bands(), 512);
+        let mut fft = config.build(cons).unwrap();
+        prod.push_slice(&samples);
+        fft.update(Duration::ZERO);
+        assert_eq!(Analyzer::bands(&fft), &expected[..]);
+        assert_eq!(Analyzer::band_frequencies(&fft)[127], 1000.0);
+
+        // Changing the scale keeps the history and rebuilds the spectrum
+        let config = fft.config().clone().with_scale(Scale::Linear);
+        fft.reconfigure(config).unwrap();
+        prod.push_slice(&samples);
+        fft.update(Duration::ZERO);
+        let linear = Analyzer::bands(&fft);
+        assert_eq!(linear.iter().cloned().fold(0.0, f32::max), 1.0);
+    }
+
+    #[test]
+    fn filter_bank_scales_and_normalizes_bands() {
+        let samples = sine(440.0, 8000.0, 4096);
+        let config = FilterBankConfig::new(1024, 12, 440.0, 880.0).with_sample_rate(8000.0);
+        let bands = |config: FilterBankConfig| {
+            let (mut prod, cons) = HeapRb::<f32>::new(4096).split();
+            let mut filter_bank = config.build(cons).unwrap();
+            prod.push_slice(&samples);
+            filter_bank.update(Duration::ZERO);
+            (
+                Analyzer::bands(&filter_bank).to_vec(),
+                *filter_bank.chroma(),
+            )
+        };
+        let (linear, chroma) = bands(config.clone());
+        let (dbfs, dbfs_chroma) = bands(config.clone().with_scale(Scale::Dbfs { floor: -120.0 }));
+        for (db, rms) in dbfs.iter().zip(linear.iter()) {
+            let expected = (20.0 * (rms * 2f32.sqrt()).log10()).max(-120.0);
+            assert!((db - expected).abs() < 1e-3, "{} != {}", db, expected);
+        }
+        assert_eq!(chroma, dbfs_chroma);
+        let (fixed, _) = bands(config.with_normalization(Normalization::Fixed { reference: 0.5 }));
+        for (value, rms) in fixed.iter().zip(linear.iter()) {
+            assert!((value - rms / 0.5).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_smoothing() {
+        assert_eq!(
+            FftConfig::new(64, 1).with_smoothing(-1.0, 0.1).validate(),
+            Err(ConfigError::Smoothing(SmoothingError {
+                attack: -1.0,
+                release: 0.1
+            }))
+        );
+        let config = FilterBankConfig::new(256, 12, 440.0, 880.0).with_sample_rate(8000.0);
+        assert_eq!(
+            config.clone().with_smoothing(0.1, f32::INFINITY).validate(),
+            Err(ConfigError::Smoothing(SmoothingError {
+                attack: 0.1,
+                release: f32::INFINITY
+            }))
+        );
+
+        let (_, cons) = HeapRb::<f32>::new(512).split();
+        let mut filter_bank = config.build(cons).unwrap();
+        let invalid = filter_bank.config().clone().with_smoothing(f32::NAN, 0.1);
+        assert!(matches!(
+            filter_bank.reconfigure(invalid),
+            Err(ConfigError::Smoothing(_))
+        ));
+        assert_eq!(filter_bank.config().smoothing(), (0.25, 0.25));
+    }
+
+    #[test]
+    fn reconfigures_without_dropping_the_ring_buffer() {
+        let (mut prod, cons) = HeapRb::<f32>::new(512).split();
+        let mut fft = FftConfig::new(64, 1).build(cons).unwrap();
+        prod.push_slice(&ramp(64));
+        fft.update(Duration::ZERO);
+        assert_eq!(Analyzer::bands(&fft).len(), 32);
+
+        let config = fft.config().clone().with_bands(200);
+        assert!(fft.reconfigure(config).is_err());
+        assert_eq!(fft.config().frame_len(), 64);
+
+        // Samples waiting in the ring buffer are analysed with the new settings
+        let samples = ramp(256);
+        prod.push_slice(&samples);
+        fft.reconfigure(FftConfig::new(256, 1).with_bands(100))
+            .unwrap();
+        fft.update(Duration::ZERO);
+        assert_eq!(fft.frames(), 2);
+        assert_eq!(Analyzer::band_frequencies(&fft).len(), 100);
+
+        let mut fs = FrequencySpectrum::new(256, 1);
+        assert_eq!(
+            Analyzer::bands(&fft),
+            &fs.frequency_spectrum(&samples)[..100]
+        );
+    }
+
+    #[test]
+    fn filter_bank_smoothing_changes_keep_the_partial_frame() {
+        let (mut prod, cons) = HeapRb::<f32>::new(512).split();
+        let mut filter_bank = FilterBankConfig::new(128, 12, 440.0, 880.0)
+            .with_sample_rate(8000.0)
+            .build(cons)
+            .unwrap();
+        assert_eq!(Analyzer::bands(&filter_bank).len(), 12);
+
+        prod.push_slice(&ramp(100));
+        filter_bank.update(Duration::ZERO);
+        assert!(!filter_bank.frame_ready());
+
+        let config = filter_bank.config().clone().with_smoothing(2.0, 2.0);
+        filter_bank.reconfigure(config).unwrap();
+        prod.push_slice(&ramp(28));
+        filter_bank.update(Duration::from_millis(500));
+        assert!(filter_bank.frame_ready());
+        // A quarter of the time constant
+        let alpha = 1.0 - (-0.25f32).exp();
+        for (smoothed, band) in Analyzer::smoothed(&filter_bank)
+            .iter()
+            .zip(Analyzer::bands(&filter_bank))
+        {
+            assert!((smoothed - alpha * band).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn onsets_keep_their_time_across_reconfigures() {
+        let (mut prod, cons) = HeapRb::<f32>::new(16384).split();
+        let config = FftConfig::new(512, 1)
+            .with_hop(256)
+            .with_sample_rate(8000.0);
+        let frame_rate = config.frame_rate();
+        let mut fft = config
+            .build(cons)
+            .unwrap()
+            .with_onset_detector(OnsetDetector::default())
+            .with_beat_tracker(BeatTracker::new(frame_rate));
+        // One second with clicks at 0.25 s and 0.5 s, twice
+        let mut second = vec![0.0; 8000];
+        for click in [2000, 4000] {
+            second[click..click + 40].fill(1.0);
+        }
+        let mut times = Vec::new();
+        prod.push_slice(&second);
+        fft.update(Duration::ZERO);
+        times.extend(fft.drain_onsets().map(|onset| onset.time.as_secs_f32()));
+
+        fft.reconfigure(fft.config().clone().with_hop(128)).unwrap();
+        assert_eq!(fft.beat_tracker.as_ref().unwrap().frame_rate(), 62.5);
+        fft.reconfigure(
+            FftConfig::new(1024, 1)
+                .with_hop(512)
+                .with_sample_rate(8000.0),
+        )
+        .unwrap();
+        assert_eq!(fft.beat_tracker.as_ref().unwrap().frame_rate(), 15.625);
+        prod.push_slice(&second);
+        fft.update(Duration::ZERO);
+        times.extend(fft.drain_onsets().map(|onset| onset.time.as_secs_f32()));
+
+        assert_eq!(times.len(), 4, "{:?}", times);
+        for (time, expected) in times.iter().zip([0.25, 0.5, 1.25, 1.5]) {
+            assert!((time - expected).abs() < 0.064, "{} != {}", time, expected);
+        }
+    }
+
+    #[test]
+    fn filter_bank_matches_the_const_generic_consumer() {
+        let samples = sine(440.0, 8000.0, 4096);
+        let (mut prod, cons) = HeapRb::<f32>::new(4096).split();
+        let mut fixed = FilterBankConsumer::<1024, 48, 4, _>::new(cons, 8000.0, 110.0, 1760.0)
+            .with_key_window(2);
+        prod.push_slice(&samples);
+        fixed.update(Duration::from_millis(100));
+
+        let (mut prod, cons) = HeapRb::<f32>::new(4096).split();
+        let mut runtime = FilterBankConfig::new(1024, 48, 110.0, 1760.0)
+            .with_sample_rate(8000.0)
+            .with_smoothing(0.25, 0.25)
+            .build(cons)
+            .unwrap()
+            .with_key_window(2);
+        prod.push_slice(&samples);
+        runtime.update(Duration::from_millis(100));
+
+        assert_eq!(Analyzer::bands(&runtime), Analyzer::bands(&fixed));
+        assert_eq!(Analyzer::smoothed(&runtime), Analyzer::smoothed(&fixed));
+        assert_eq!(runtime.chroma(), &fixed.chroma);
+        assert_eq!(runtime.key(), fixed.key);
+        assert_eq!(runtime.chord(), fixed.chord);
+        assert_eq!(runtime.tuning(), fixed.tuning());
+        // A is the loudest pitch class
+        let loudest = (0..12)
+            .max_by(|&a, &b| runtime.chroma()[a].total_cmp(&runtime.chroma()[b]))
+            .unwrap();
+        assert_eq!(loudest, 9);
+    }
+}
@@ -0,0 +1,281 @@
+use std::{fmt, time::Duration};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SmoothingMode {
+    /// Rises with the attack time constant and falls with the release one
+    #[default]
+    AttackRelease,
+    /// Rises like [`SmoothingMode::AttackRelease`], then holds each peak for the given time
+    /// before releasing it
+    PeakHold(Duration),
+}
+
+/// Time constants rejected by [`Smoother::try_new`], negative, infinite or NaN.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SmoothingError {
+    pub attack: f32,
+    pub release: f32,
+}
+
+impl fmt::Display for SmoothingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "attack ({}) and release ({}) must be finite and not negative",
+            self.attack, self.release
+        )
+    }
+}
+
+impl std::error::Error for SmoothingError {}
+
+/// Exponential smoothing of band values over real time.
+///
+/// Each update moves the values towards their target by `1 - exp(-elapsed / tau)`, with
+/// `tau` the attack time constant when rising and the release one when falling, so the
+/// result doesn't depend on how the elapsed time is split between updates and never
+/// overshoots the target.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Smoother {
+    /// Time constant in seconds when rising
+    attack: f64,
+    /// Time constant in seconds when falling
+    release: f64,
+    mode: SmoothingMode,
+    /// Seconds since the peak of each band in [`SmoothingMode::PeakHold`]
+    since_peak: Vec<f64>,
+}
+
+impl Smoother {
+    /// Smooths with `attack` and `release` time constants in seconds, 0 follows the target.
+    ///
+    /// Meant for constant time constants, use [`Smoother::try_new`] for values chosen at runtime.
+    ///
+    /// # Panics
+    ///
+    /// If a time constant is negative, infinite or NaN.
+    pub fn new(attack: f32, release: f32) -> Self {
+        Smoother::try_new(attack, release).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Like [`Smoother::new`], but returns an error for a negative, infinite or NaN
+    /// time constant.
+    pub fn try_new(attack: f32, release: f32) -> Result<Self, SmoothingError> {
+        let valid = |tau: f32| tau.is_finite() && tau >= 0.0;
+        if !(valid(attack) && valid(release)) {
+            return Err(SmoothingError { attack, release });
+        }
+        Ok(Smoother {
+            attack: attack as f64,
+            release: release as f64,
+            mode: SmoothingMode::default(),
+            since_peak: Vec::new(),
+        })
+    }
+
+    /// Moves towards the target at `rate` per second, i.e. a time constant of `1 / rate`
+    /// both ways.
+    ///
+    /// This is the smoothing of the `DELTA` parameter of the consumers.
+    ///
+    /// # Panics
+    ///
+    /// If `rate` is not positive.
+    pub fn from_rate(rate: f32) -> Self {
+        Smoother::new(1.0 / rate, 1.0 / rate)
+    }
+
+    pub fn with_mode(mut self, mode: SmoothingMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Attack time constant in seconds.
+    pub fn attack(&self) -> f32 {
+        self.attack as f32
+    }
+
+    /// Release time constant in seconds.
+    pub fn release(&self) -> f32 {
+        self.release as f32
+    }
+
+    pub fn mode(&self) -> SmoothingMode {
+        self.mode
+    }
+
+    /// Forgets the held peaks.
+    pub fn reset(&mut self) {
+        self.since_peak.clear();
+    }
+
+    /// Moves `smoothed` towards `target` over `elapsed`, the time since the previous call.
+    ///
+    /// # Panics
+    ///
+    /// If `smoothed` and `target` have different lengths.
+    pub fn smooth(&mut self, smoothed: &mut [f32], target: &[f32], elapsed: Duration) {
+        if smoothed.len() != target.len() {
+            panic!(
+                "smoothed ({}) and target ({}) lengths differ",
+                smoothed.len(),
+                target.len()
+            )
+        }
+        let dt = elapsed.as_secs_f64();
+        let hold = match self.mode {
+            SmoothingMode::AttackRelease => {
+                for (value, &target) in smoothed.iter_mut().zip(target.iter()) {
+                    let tau = if target > *value {
+                        self.attack
+                    } else {
+                        self.release
+                    };
+                    *value = approach(*value, target, dt, tau);
+                }
+                return;
+            }
+            SmoothingMode::PeakHold(hold) => hold.as_secs_f64(),
+        };
+        self.since_peak.resize(smoothed.len(), 0.0);
+        for ((value, &target), since_peak) in smoothed
+            .iter_mut()
+            .zip(target.iter())
+            .zip(self.since_peak.iter_mut())
+        {
+            if target >= *value {
+                *value = approach(*value, target, dt, self.attack);
+                *since_peak = 0.0;
+            } else {
+                // Only the part of `elapsed` past the hold time releases the peak
+                let released = (*since_peak + dt - hold).clamp(0.0, dt);
+                *value = approach(*value, target, released, self.release);
+                *since_peak += dt;
+            }
+        }
+    }
+}
+
+impl Default for Smoother {
+    /// The smoothing of a `DELTA` of 4, a time constant of 250 ms.
+    fn default() -> Self {
+        Smoother::from_rate(4.0)
+    }
+}
+
+/// Moves `value` towards `target` over `dt` seconds with time constant `tau`.
+fn approach(value: f32, target: f32, dt: f64, tau: f64) -> f32 {
+    let alpha = if tau > 0.0 {
+        -(-dt / tau).exp_m1()
+    } else {
+        1.0
+    };
+    value + (target - value) * alpha as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(smoother: &mut Smoother, start: f32, target: f32, steps: &[Duration]) -> f32 {
+        let mut smoothed = [start];
+        for &step in steps {
+            smoother.smooth(&mut smoothed, &[target], step);
+        }
+        smoothed[0]
+    }
+
+    #[test]
+    fn large_steps_never_overshoot() {
+        for step in [
+            Duration::from_secs(1),
+            Duration::from_secs(3600),
+            Duration::MAX,
+        ] {
+            let mut smoother = Smoother::from_rate(100.0);
+            assert_eq!(run(&mut smoother, 0.0, 1.0, &[step]), 1.0);
+            assert_eq!(run(&mut smoother, 1.0, 0.0, &[step]), 0.0);
+        }
+        // Exactly one time constant
+        let mut smoother = Smoother::new(0.5, 0.5);
+        let value = run(&mut smoother, 0.0, 1.0, &[Duration::from_millis(500)]);
+        assert!((value - (1.0 - (-1.0f32).exp())).abs() < 1e-6);
+    }
+
+    #[test]
+    fn tiny_steps_add_up_like_one_step() {
+        let mut smoother = Smoother::new(0.1, 0.1);
+        let one = run(&mut smoother, 0.0, 1.0, &[Duration::from_millis(100)]);
+        // Sub millisecond steps still move the values
+        let nanos = run(&mut smoother, 0.0, 1.0, &[Duration::from_nanos(1)]);
+        assert!(nanos > 0.0);
+        let many = run(
+            &mut smoother,
+            0.0,
+            1.0,
+            &vec![Duration::from_micros(100); 1000],
+        );
+        assert!((one - many).abs() < 1e-4, "{} != {}", one, many);
+        assert_eq!(run(&mut smoother, 0.3, 1.0, &[Duration::ZERO]), 0.3);
+    }
+
+    #[test]
+    fn attack_and_release_are_independent() {
+        let mut smoother = Smoother::new(0.0, 1.0);
+        let step = [Duration::from_millis(10)];
+        assert_eq!(run(&mut smoother, 0.0, 1.0, &step), 1.0);
+        let released = run(&mut smoother, 1.0, 0.0, &step);
+        assert!(released > 0.98 && released < 1.0);
+    }
+
+    #[test]
+    fn peak_hold_decays_after_the_hold_time() {
+        let mut smoother =
+            Smoother::new(0.0, 0.1).with_mode(SmoothingMode::PeakHold(Duration::from_millis(200)));
+        let mut smoothed = [0.0];
+        let step = Duration::from_millis(50);
+        smoother.smooth(&mut smoothed, &[1.0], step);
+        assert_eq!(smoothed[0], 1.0);
+        for _ in 0..4 {
+            smoother.smooth(&mut smoothed, &[0.0], step);
+            assert_eq!(smoothed[0], 1.0);
+        }
+        smoother.smooth(&mut smoothed, &[0.0], step);
+        assert!((smoothed[0] - (-0.5f32).exp()).abs() < 1e-6);
+
+        // A step crossing the end of the hold only releases past it
+        smoother.smooth(&mut smoothed, &[1.0], step);
+        smoother.smooth(&mut smoothed, &[0.0], Duration::from_millis(300));
+        assert!((smoothed[0] - (-1.0f32).exp()).abs() < 1e-6);
+    }
+
+    #[test]
+    #[should_panic]
+    fn negative_time_constants_panic() {
+        Smoother::new(-1.0, 0.1);
+    }
+
+    #[test]
+    fn invalid_time_constants_are_errors() {
+        assert!(Smoother::try_new(0.0, 2.0).is_ok());
+        for (attack, release) in [
+            (-0.1, 0.1),
+            (0.1, f32::NAN),
+            (f32::INFINITY, 0.1),
+            (0.1, f32::NEG_INFINITY),
+        ] {
+            assert!(
+                Smoother::try_new(attack, release).is_err(),
+                "{} {}",
+                attack,
+                release
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn a_rate_of_zero_panics() {
+        Smoother::from_rate(0.0);
+    }
+}
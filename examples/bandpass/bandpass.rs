@@ -1,8 +1,7 @@
 use std::time::Duration;
 
 use audio_streams::{
-    bandpass::{AudioConsumerFilterBankF32, FilterBankConsumer},
-    AudioProducerF32, InputModel,
+    bandpass::FilterBankConsumer, Analyzer, AudioProducerF32, FftConsumer, InputModel,
 };
 use nannou::prelude::*;
 use nannou_audio::{self as audio, Buffer};
@@ -26,7 +25,8 @@ fn main() {
 
 pub struct Model {
     pub audio_in: audio::Stream<AudioProducerF32>,
-    pub filter_bank: AudioConsumerFilterBankF32<IB_LEN, FB_LEN, DELTA>,
+    /// Filter bank, or FFT when started with `fft` as first argument
+    pub analyzer: Box<dyn Analyzer>,
     pub elapsed: Duration,
     fft_history: [[f32; FB_LEN]; HISTORY_LEN],
     history_index: usize,
@@ -40,7 +40,7 @@ pub struct Model {
 
 impl Model {
     pub fn update(&mut self, milis: Duration) {
-        self.filter_bank.update(milis);
+        self.analyzer.update(milis);
     }
 }
 
@@ -48,7 +48,7 @@ fn update(_app: &App, model: &mut Model, update: Update) {
     let milis = update.since_last;
     model.update(milis);
 
-    let new_fft_data = mutate_uniforms(&model.filter_bank.smoothed);
+    let new_fft_data = mutate_uniforms(model.analyzer.smoothed());
     model.fft_history[model.history_index] = new_fft_data;
     model.history_index = (model.history_index + 1) % HISTORY_LEN;
 }
@@ -108,12 +108,19 @@ fn model(app: &App) -> Model {
         .build()
         .unwrap();
 
-    let output_model = FilterBankConsumer::new(
-        cons,
-        in_stream.cpal_config().sample_rate.0 as f32,
-        27.5,
-        4186.0,
-    );
+    let sample_rate = in_stream.cpal_config().sample_rate.0 as f32;
+    let analyzer: Box<dyn Analyzer> = match std::env::args().nth(1).as_deref() {
+        Some("fft") => Box::new(
+            FftConsumer::<IB_LEN, FB_LEN, DELTA, _>::new(cons, in_stream.cpal_config().channels)
+                .with_sample_rate(sample_rate),
+        ),
+        _ => Box::new(FilterBankConsumer::<IB_LEN, FB_LEN, DELTA, _>::new(
+            cons,
+            sample_rate,
+            27.5,
+            4186.0,
+        )),
+    };
 
     // Start input stream
     in_stream.play().unwrap();
@@ -176,7 +183,7 @@ fn model(app: &App) -> Model {
 
     Model {
         audio_in: in_stream,
-        filter_bank: output_model,
+        analyzer,
         elapsed: Duration::from_secs(0),
         fft_history: [[0.0; FB_LEN]; HISTORY_LEN],
         history_index: 0,
@@ -188,10 +195,10 @@ fn model(app: &App) -> Model {
     }
 }
 
-fn mutate_uniforms(u: &[f32; FB_LEN]) -> [f32; FB_LEN] {
+fn mutate_uniforms(u: &[f32]) -> [f32; FB_LEN] {
     let mut uniforms = [0.0; FB_LEN];
-    for i in 0..FB_LEN {
-        uniforms[i] = u[i];
+    for (uniform, value) in uniforms.iter_mut().zip(u.iter()) {
+        *uniform = *value;
     }
     uniforms
 }
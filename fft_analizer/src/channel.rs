@@ -0,0 +1,65 @@
+/// Which spectra [`crate::FrequencySpectrum`] computes from interleaved input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChannelMode {
+    /// A single spectrum of the average of all channels.
+    #[default]
+    Mono,
+    /// One spectrum per input channel.
+    PerChannel,
+    /// A single spectrum of the given channel, starting at 0.
+    Channel(u16),
+    /// Two spectra of stereo input: mid `(L + R) / 2` and side `(L - R) / 2`.
+    MidSide,
+}
+
+impl ChannelMode {
+    /// Number of spectra computed for input with `channels` channels.
+    pub fn outputs(&self, channels: u16) -> usize {
+        match self {
+            ChannelMode::Mono | ChannelMode::Channel(_) => 1,
+            ChannelMode::PerChannel => channels as usize,
+            ChannelMode::MidSide => 2,
+        }
+    }
+}
+
+/// One magnitude spectrum per output channel of a [`ChannelMode`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChannelSpectra {
+    num_channels: usize,
+    spectrum_len: usize,
+    data: Vec<f32>,
+}
+
+impl ChannelSpectra {
+    pub(crate) fn new(num_channels: usize, spectrum_len: usize) -> Self {
+        ChannelSpectra {
+            num_channels,
+            spectrum_len,
+            data: vec![0.0; num_channels * spectrum_len],
+        }
+    }
+
+    pub fn num_channels(&self) -> usize {
+        self.num_channels
+    }
+
+    /// Number of bins in each spectrum.
+    pub fn spectrum_len(&self) -> usize {
+        self.spectrum_len
+    }
+
+    /// Spectrum of output channel `index`.
+    pub fn channel(&self, index: usize) -> &[f32] {
+        &self.data[index * self.spectrum_len..(index + 1) * self.spectrum_len]
+    }
+
+    pub(crate) fn channel_mut(&mut self, index: usize) -> &mut [f32] {
+        &mut self.data[index * self.spectrum_len..(index + 1) * self.spectrum_len]
+    }
+
+    /// Spectra of every output channel in order.
+    pub fn iter(&self) -> impl Iterator<Item = &[f32]> {
+        (0..self.num_channels).map(|index| self.channel(index))
+    }
+}
@@ -0,0 +1,320 @@
+use std::fmt;
+
+use crate::chroma::PITCH_CLASSES;
+
+/// Scales template similarities into log-likelihoods for the Viterbi smoothing.
+const CONCENTRATION: f32 = 5.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChordQuality {
+    Major,
+    Minor,
+    Dominant7,
+    Diminished,
+    Augmented,
+}
+
+impl ChordQuality {
+    const ALL: [ChordQuality; 5] = [
+        ChordQuality::Major,
+        ChordQuality::Minor,
+        ChordQuality::Dominant7,
+        ChordQuality::Diminished,
+        ChordQuality::Augmented,
+    ];
+
+    /// Intervals from the root in semitones.
+    pub fn intervals(&self) -> &'static [usize] {
+        match self {
+            ChordQuality::Major => &[0, 4, 7],
+            ChordQuality::Minor => &[0, 3, 7],
+            ChordQuality::Dominant7 => &[0, 4, 7, 10],
+            ChordQuality::Diminished => &[0, 3, 6],
+            ChordQuality::Augmented => &[0, 4, 8],
+        }
+    }
+
+    /// Suffix appended to the root in chord names.
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            ChordQuality::Major => "",
+            ChordQuality::Minor => "m",
+            ChordQuality::Dominant7 => "7",
+            ChordQuality::Diminished => "dim",
+            ChordQuality::Augmented => "aug",
+        }
+    }
+}
+
+/// A chord, displayed like `C`, `F#m` or `G7`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chord {
+    /// Pitch class of the root, 0 is C
+    pub root: usize,
+    pub quality: ChordQuality,
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", PITCH_CLASSES[self.root], self.quality.suffix())
+    }
+}
+
+/// Chord recognition by template matching on chroma frames.
+///
+/// Each frame is compared with a binary template of every chord and with a flat
+/// "no chord" template, using cosine similarity. Without smoothing each frame gets
+/// the best matching chord. With smoothing the chords are the states of an HMM that
+/// prefers staying on the same chord, decoded online by [`ChordRecognizer::push`]
+/// or over a whole sequence by [`ChordRecognizer::viterbi`].
+#[derive(Debug, Clone)]
+pub struct ChordRecognizer {
+    /// `None` is the no chord state, it is the last one
+    states: Vec<Option<Chord>>,
+    templates: Vec<[f32; 12]>,
+    /// Probability of staying on the same chord between frames, `None` without smoothing
+    self_transition: Option<f32>,
+    /// Log-probability of the best path ending on each state
+    scores: Vec<f32>,
+    similarities: Vec<f32>,
+}
+
+impl Default for ChordRecognizer {
+    fn default() -> Self {
+        ChordRecognizer::new()
+    }
+}
+
+impl ChordRecognizer {
+    /// Creates a recognizer without smoothing.
+    pub fn new() -> Self {
+        let mut states: Vec<Option<Chord>> = ChordQuality::ALL
+            .iter()
+            .flat_map(|&quality| (0..12).map(move |root| Some(Chord { root, quality })))
+            .collect();
+        states.push(None);
+        let templates = states
+            .iter()
+            .map(|state| match state {
+                Some(chord) => {
+                    let mut template = [0.0; 12];
+                    for interval in chord.quality.intervals() {
+                        template[(chord.root + interval) % 12] = 1.0;
+                    }
+                    template
+                }
+                None => [1.0; 12],
+            })
+            .collect();
+        ChordRecognizer {
+            scores: vec![0.0; states.len()],
+            similarities: vec![0.0; states.len()],
+            states,
+            templates,
+            self_transition: None,
+        }
+    }
+
+    /// Smooths the chord sequence with an HMM staying on the same chord with
+    /// probability `self_transition`, e.g. 0.9. Higher values need more evidence to change chord.
+    ///
+    /// # Panics
+    ///
+    /// If `self_transition` is not in `0.0..1.0`.
+    pub fn with_smoothing(mut self, self_transition: f32) -> Self {
+        if !(0.0..1.0).contains(&self_transition) {
+            panic!(
+                "self transition probability ({}) must be in 0..1",
+                self_transition
+            )
+        }
+        self.self_transition = Some(self_transition);
+        self
+    }
+
+    /// Best matching chord of a single frame, `None` when no chord matches better than
+    /// a flat chroma or the frame is silent.
+    pub fn recognize(&mut self, chroma: &[f32; 12]) -> Option<Chord> {
+        self.compute_similarities(chroma);
+        let best = argmax(&self.similarities);
+        self.states[best]
+    }
+
+    /// Adds a frame and returns its chord.
+    ///
+    /// With smoothing, returns the last state of the most likely path so far.
+    pub fn push(&mut self, chroma: &[f32; 12]) -> Option<Chord> {
+        let Some(self_transition) = self.self_transition else {
+            return self.recognize(chroma);
+        };
+        self.compute_similarities(chroma);
+        let (stay, switch) = self.transition_costs(self_transition);
+        let best_previous = self.scores[argmax(&self.scores)];
+        for (score, similarity) in self.scores.iter_mut().zip(self.similarities.iter()) {
+            *score = (*score + stay).max(best_previous + switch) + CONCENTRATION * similarity;
+        }
+        // Keeps the scores bounded
+        let best = argmax(&self.scores);
+        let offset = self.scores[best];
+        self.scores.iter_mut().for_each(|score| *score -= offset);
+        self.states[best]
+    }
+
+    /// Decodes the most likely chord sequence of `frames` with the Viterbi algorithm.
+    ///
+    /// Uses a self transition probability of 0.9 when smoothing is not enabled.
+    /// Doesn't change the state used by [`ChordRecognizer::push`].
+    pub fn viterbi(&mut self, frames: &[[f32; 12]]) -> Vec<Option<Chord>> {
+        let (stay, switch) = self.transition_costs(self.self_transition.unwrap_or(0.9));
+        let len = self.states.len();
+        let mut scores = vec![0.0; len];
+        let mut next = vec![0.0; len];
+        // For each frame and state, the state of the previous frame on the best path
+        let mut backpointers = vec![0; frames.len() * len];
+        for (t, chroma) in frames.iter().enumerate() {
+            self.compute_similarities(chroma);
+            let best_previous = argmax(&scores);
+            for state in 0..len {
+                let (score, from) =
+                    if t == 0 || scores[state] + stay >= scores[best_previous] + switch {
+                        (scores[state] + stay, state)
+                    } else {
+                        (scores[best_previous] + switch, best_previous)
+                    };
+                next[state] = score + CONCENTRATION * self.similarities[state];
+                backpointers[t * len + state] = from;
+            }
+            std::mem::swap(&mut scores, &mut next);
+        }
+
+        let mut path = vec![None; frames.len()];
+        let mut state = argmax(&scores);
+        for t in (0..frames.len()).rev() {
+            path[t] = self.states[state];
+            state = backpointers[t * len + state];
+        }
+        path
+    }
+
+    /// Log-probabilities of staying on a chord and of switching to a given other one.
+    fn transition_costs(&self, self_transition: f32) -> (f32, f32) {
+        let others = (self.states.len() - 1) as f32;
+        (
+            self_transition.ln(),
+            ((1.0 - self_transition) / others).ln(),
+        )
+    }
+
+    fn compute_similarities(&mut self, chroma: &[f32; 12]) {
+        let norm = chroma.iter().map(|v| v * v).sum::<f32>().sqrt();
+        for (similarity, template) in self.similarities.iter_mut().zip(self.templates.iter()) {
+            *similarity = if norm > 0.0 {
+                let dot: f32 = chroma.iter().zip(template.iter()).map(|(c, t)| c * t).sum();
+                let template_norm = template.iter().sum::<f32>().sqrt();
+                dot / (norm * template_norm)
+            } else {
+                0.0
+            };
+        }
+        // Silence matches no chord
+        if norm <= 0.0 {
+            let last = self.similarities.len() - 1;
+            self.similarities[last] = 1.0;
+        }
+    }
+}
+
+/// Index of the highest value, the first one on ties.
+fn argmax(values: &[f32]) -> usize {
+    let mut best = 0;
+    for (i, value) in values.iter().enumerate() {
+        if *value > values[best] {
+            best = i;
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chroma(chord: Chord) -> [f32; 12] {
+        let mut chroma = [0.05; 12];
+        for interval in chord.quality.intervals() {
+            chroma[(chord.root + interval) % 12] = 1.0;
+        }
+        chroma
+    }
+
+    fn chord(root: usize, quality: ChordQuality) -> Chord {
+        Chord { root, quality }
+    }
+
+    #[test]
+    fn recognizes_every_template() {
+        let mut recognizer = ChordRecognizer::new();
+        for quality in ChordQuality::ALL {
+            // Augmented chords are symmetric, so only their first root is distinguishable
+            let roots = if quality == ChordQuality::Augmented {
+                4
+            } else {
+                12
+            };
+            for root in 0..roots {
+                let expected = chord(root, quality);
+                assert_eq!(recognizer.recognize(&chroma(expected)), Some(expected));
+            }
+        }
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(chord(0, ChordQuality::Major).to_string(), "C");
+        assert_eq!(chord(6, ChordQuality::Minor).to_string(), "F#m");
+        assert_eq!(chord(7, ChordQuality::Dominant7).to_string(), "G7");
+        assert_eq!(chord(11, ChordQuality::Diminished).to_string(), "Bdim");
+        assert_eq!(chord(8, ChordQuality::Augmented).to_string(), "G#aug");
+    }
+
+    #[test]
+    fn flat_chroma_and_silence_are_no_chord() {
+        let mut recognizer = ChordRecognizer::new();
+        assert_eq!(recognizer.recognize(&[1.0; 12]), None);
+        assert_eq!(recognizer.recognize(&[0.0; 12]), None);
+    }
+
+    /// C for 10 frames with a single F frame, then G for 10 frames.
+    fn sequence() -> Vec<[f32; 12]> {
+        let c = chroma(chord(0, ChordQuality::Major));
+        let f = chroma(chord(5, ChordQuality::Major));
+        let g = chroma(chord(7, ChordQuality::Major));
+        let mut frames = vec![c; 10];
+        frames[5] = f;
+        frames.extend(vec![g; 10]);
+        frames
+    }
+
+    #[test]
+    fn smoothing_ignores_single_frame_changes() {
+        let frames = sequence();
+        let mut raw = ChordRecognizer::new();
+        let labels: Vec<Option<Chord>> = frames.iter().map(|frame| raw.push(frame)).collect();
+        assert_eq!(labels[5], Some(chord(5, ChordQuality::Major)));
+
+        let mut smoothed = ChordRecognizer::new().with_smoothing(0.9);
+        let labels: Vec<Option<Chord>> = frames.iter().map(|frame| smoothed.push(frame)).collect();
+        assert_eq!(labels[5], Some(chord(0, ChordQuality::Major)));
+        // The online decoding follows the change within a few frames
+        assert_eq!(labels[14], Some(chord(7, ChordQuality::Major)));
+    }
+
+    #[test]
+    fn viterbi_decodes_the_whole_sequence() {
+        let path = ChordRecognizer::new().viterbi(&sequence());
+        let c = Some(chord(0, ChordQuality::Major));
+        let g = Some(chord(7, ChordQuality::Major));
+        assert_eq!(path[..10], [c; 10]);
+        assert_eq!(path[10..], [g; 10]);
+    }
+}
@@ -0,0 +1,377 @@
+use std::f32::consts::PI;
+
+use rustfft::num_complex::Complex;
+
+use crate::{
+    peaks::{Peak, PeakFinder},
+    FrequencyAxis,
+};
+
+/// Pitch-class names of the chroma bins, starting at C.
+pub const PITCH_CLASSES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// Number of spectral peaks each frame contributes to the tuning estimate.
+const TUNING_PEAKS: usize = 8;
+/// Peaks quieter than this fraction of the loudest one don't contribute to the tuning estimate.
+const TUNING_PEAK_RATIO: f32 = 0.1;
+
+/// How the 12 chroma values are rescaled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChromaNormalization {
+    /// Energy per pitch class.
+    None,
+    /// The loudest pitch class is 1.
+    #[default]
+    Max,
+    /// Values sum to 1.
+    Sum,
+    /// The vector has unit length.
+    Euclidean,
+}
+
+/// Folds a spectrum or filter bank output into 12 pitch classes.
+///
+/// Each bin or band adds its energy to the pitch class nearest to its frequency,
+/// on a grid tuned to `reference` (A4) shifted by the tuning. Unless a fixed tuning
+/// is set, the tuning follows the deviation of the spectral peaks from the grid,
+/// averaged over the last frames.
+///
+/// Bins wider than a semitone can't be assigned to a single pitch class, so the range
+/// should start where the FFT resolution is below a semitone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chromagram {
+    reference: f32,
+    f_min: f32,
+    f_max: f32,
+    normalization: ChromaNormalization,
+    /// `None` while the tuning is estimated
+    fixed_tuning: Option<f32>,
+    /// Weight of the previous frames in the tuning estimate
+    tuning_decay: f32,
+    /// Sum of the peak deviations as amplitude weighted phasors, one turn per semitone
+    deviations: Complex<f32>,
+    tuning: f32,
+    chroma: [f32; 12],
+    peaks: Vec<Peak>,
+}
+
+impl Default for Chromagram {
+    fn default() -> Self {
+        Chromagram::new()
+    }
+}
+
+impl Chromagram {
+    /// Creates a chromagram over 27.5 Hz to 8 kHz, tuned to A4 = 440 Hz with estimated tuning.
+    pub fn new() -> Self {
+        Chromagram {
+            reference: 440.0,
+            f_min: 27.5,
+            f_max: 8000.0,
+            normalization: ChromaNormalization::default(),
+            fixed_tuning: None,
+            tuning_decay: 0.95,
+            deviations: Complex::new(0.0, 0.0),
+            tuning: 0.0,
+            chroma: [0.0; 12],
+            peaks: Vec::new(),
+        }
+    }
+
+    /// Nominal frequency of A4 in Hz, the tuning is measured from it.
+    ///
+    /// # Panics
+    ///
+    /// If `reference` is not finite and positive.
+    pub fn with_reference(mut self, reference: f32) -> Self {
+        if !(reference.is_finite() && reference > 0.0) {
+            panic!("reference ({}) must be finite and positive", reference)
+        }
+        self.reference = reference;
+        self
+    }
+
+    /// Only bins and bands within `f_min..=f_max` contribute.
+    ///
+    /// # Panics
+    ///
+    /// If `f_min` is not positive or is greater than `f_max`.
+    pub fn with_range(mut self, f_min: f32, f_max: f32) -> Self {
+        if f_min <= 0.0 || f_min > f_max {
+            panic!("invalid frequency range {}..={}", f_min, f_max)
+        }
+        self.f_min = f_min;
+        self.f_max = f_max;
+        self
+    }
+
+    pub fn with_normalization(mut self, normalization: ChromaNormalization) -> Self {
+        self.normalization = normalization;
+        self
+    }
+
+    /// Uses a fixed tuning in cents instead of estimating it.
+    pub fn with_tuning(mut self, cents: f32) -> Self {
+        self.fixed_tuning = Some(cents);
+        self.tuning = cents;
+        self
+    }
+
+    /// Weight, in `0..=1`, of the previous frames in the tuning estimate. Higher values
+    /// give a steadier estimate that is slower to follow a change of tuning.
+    ///
+    /// # Panics
+    ///
+    /// If `decay` is not in `0.0..=1.0`.
+    pub fn with_tuning_decay(mut self, decay: f32) -> Self {
+        if !(0.0..=1.0).contains(&decay) {
+            panic!("tuning decay ({}) must be in 0..=1", decay)
+        }
+        self.tuning_decay = decay;
+        self
+    }
+
+    /// Deviation of the tuning from the reference in cents, within ±50.
+    pub fn tuning(&self) -> f32 {
+        self.tuning
+    }
+
+    /// Frequency of A4 in Hz including the tuning.
+    pub fn a4(&self) -> f32 {
+        self.reference * 2f32.powf(self.tuning / 1200.0)
+    }
+
+    /// Chroma of the last processed frame, starting at C.
+    pub fn chroma(&self) -> &[f32; 12] {
+        &self.chroma
+    }
+
+    /// Pitch-class names with the chroma of the last processed frame.
+    pub fn labeled(&self) -> impl Iterator<Item = (&'static str, f32)> + '_ {
+        PITCH_CLASSES
+            .iter()
+            .copied()
+            .zip(self.chroma.iter().copied())
+    }
+
+    /// Computes the chroma of a linear magnitude spectrum laid out like
+    /// [`crate::FrequencySpectrum::frequency_spectrum`].
+    pub fn process_spectrum(&mut self, spectrum: &[f32], axis: FrequencyAxis) -> &[f32; 12] {
+        if self.fixed_tuning.is_none() {
+            PeakFinder::new().with_max_peaks(TUNING_PEAKS).find_into(
+                spectrum,
+                axis,
+                &mut self.peaks,
+            );
+            self.update_tuning(|peak| peak.frequency);
+        }
+        self.fold((0..spectrum.len()).map(|i| (axis.bin_frequency(i), spectrum[i])))
+    }
+
+    /// Computes the chroma of filter bank `bands` centred on `frequencies`, in ascending order.
+    ///
+    /// The tuning is estimated from peaks between neighbouring bands, which is only as
+    /// precise as the bands are narrow and overlapping: with semitone spaced bands prefer
+    /// a fixed tuning, see [`Chromagram::with_tuning`].
+    ///
+    /// # Panics
+    ///
+    /// If `bands` length is different than `frequencies` length.
+    pub fn process_bands(&mut self, bands: &[f32], frequencies: &[f32]) -> &[f32; 12] {
+        if bands.len() != frequencies.len() {
+            panic!(
+                "bands len ({}) is different than frequencies len ({})",
+                bands.len(),
+                frequencies.len()
+            )
+        }
+        if self.fixed_tuning.is_none() {
+            // Only positions are used, the axis is irrelevant
+            let axis = FrequencyAxis::new(1.0, 2);
+            PeakFinder::new()
+                .with_max_peaks(TUNING_PEAKS)
+                .find_into(bands, axis, &mut self.peaks);
+            // Bands are interpolated geometrically
+            self.update_tuning(|peak| {
+                // Peaks are never on the last band, so `i + 1` exists
+                let i = peak.position.floor() as usize;
+                let ratio = frequencies[i + 1] / frequencies[i];
+                frequencies[i] * ratio.powf(peak.position - i as f32)
+            });
+        }
+        self.fold(frequencies.iter().copied().zip(bands.iter().copied()))
+    }
+
+    /// Adds the deviation of the loudest peaks from the reference grid to the tuning estimate.
+    fn update_tuning(&mut self, frequency: impl Fn(&Peak) -> f32) {
+        self.deviations *= self.tuning_decay;
+        let loudest = self.peaks.first().map_or(0.0, |peak| peak.amplitude);
+        for peak in self.peaks.iter() {
+            if peak.amplitude < loudest * TUNING_PEAK_RATIO {
+                continue;
+            }
+            let hz = frequency(peak);
+            if hz < self.f_min || hz > self.f_max {
+                continue;
+            }
+            let cents = 1200.0 * (hz / self.reference).log2();
+            let turn = 2.0 * PI * cents / 100.0;
+            self.deviations += Complex::from_polar(peak.amplitude, turn);
+        }
+        if self.deviations.norm() > 0.0 {
+            self.tuning = self.deviations.arg() * 100.0 / (2.0 * PI);
+        }
+    }
+
+    /// Adds the energy of each `(frequency, magnitude)` pair to its pitch class and normalizes.
+    fn fold(&mut self, bins: impl Iterator<Item = (f32, f32)>) -> &[f32; 12] {
+        self.chroma = [0.0; 12];
+        let a4 = self.a4();
+        for (hz, magnitude) in bins {
+            if hz < self.f_min || hz > self.f_max {
+                continue;
+            }
+            let midi = 69.0 + 12.0 * (hz / a4).log2();
+            let class = (midi.round() as i32).rem_euclid(12) as usize;
+            self.chroma[class] += magnitude * magnitude;
+        }
+
+        let norm = match self.normalization {
+            ChromaNormalization::None => 1.0,
+            ChromaNormalization::Max => self.chroma.iter().fold(0.0, |max: f32, &v| max.max(v)),
+            ChromaNormalization::Sum => self.chroma.iter().sum(),
+            ChromaNormalization::Euclidean => self.chroma.iter().map(|v| v * v).sum::<f32>().sqrt(),
+        };
+        if norm > 0.0 {
+            self.chroma.iter_mut().for_each(|v| *v /= norm);
+        }
+        &self.chroma
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_utils, FrequencySpectrum, Normalization};
+
+    const SAMPLE_RATE: f32 = 44100.0;
+    const LEN: usize = 8192;
+
+    fn spectrum(tones: &[f32]) -> (Vec<f32>, FrequencyAxis) {
+        let amplitude = 1.0 / tones.len() as f32;
+        let tones: Vec<(f32, f32)> = tones.iter().map(|&hz| (hz, amplitude)).collect();
+        let samples = test_utils::tones(&tones, SAMPLE_RATE, LEN);
+        let mut fs = FrequencySpectrum::new(LEN, 1)
+            .with_sample_rate(SAMPLE_RATE)
+            .with_normalization(Normalization::None);
+        let spectrum = fs.frequency_spectrum(&samples).to_vec();
+        (spectrum, fs.axis())
+    }
+
+    fn loudest(chroma: &[f32; 12]) -> &'static str {
+        let i = (0..12)
+            .max_by(|&a, &b| chroma[a].total_cmp(&chroma[b]))
+            .unwrap();
+        PITCH_CLASSES[i]
+    }
+
+    fn detuned(midi: f32, cents: f32) -> f32 {
+        440.0 * 2f32.powf((midi - 69.0 + cents / 100.0) / 12.0)
+    }
+
+    #[test]
+    fn a_sine_at_440_is_an_a() {
+        let (spectrum, axis) = spectrum(&[440.0]);
+        let mut chromagram = Chromagram::new().with_range(100.0, 5000.0);
+        let chroma = chromagram.process_spectrum(&spectrum, axis);
+        assert_eq!(loudest(chroma), "A");
+        assert_eq!(chroma[9], 1.0);
+        assert!(chromagram.tuning().abs() < 2.0, "{}", chromagram.tuning());
+        let labels: Vec<&str> = chromagram.labeled().map(|(name, _)| name).collect();
+        assert_eq!(labels, PITCH_CLASSES);
+    }
+
+    #[test]
+    fn estimates_the_tuning_of_a_detuned_chord() {
+        // C major chord 30 cents sharp
+        let tones = [60.0, 64.0, 67.0].map(|midi| detuned(midi, 30.0));
+        let (spectrum, axis) = spectrum(&tones);
+        let mut chromagram = Chromagram::new().with_range(100.0, 5000.0);
+        let chroma = *chromagram.process_spectrum(&spectrum, axis);
+        assert!(
+            (chromagram.tuning() - 30.0).abs() < 3.0,
+            "{}",
+            chromagram.tuning()
+        );
+        assert!((chromagram.a4() - detuned(69.0, 30.0)).abs() < 1.0);
+        for class in [0, 4, 7] {
+            assert!(chroma[class] > 0.5, "{:?}", chroma);
+        }
+        for class in [1, 2, 3, 5, 6, 8, 9, 10, 11] {
+            assert!(chroma[class] < 0.05, "{:?}", chroma);
+        }
+    }
+
+    #[test]
+    fn tuning_moves_the_pitch_class_boundaries() {
+        // 60 cents above G is nearer to G# on a 440 grid but is a G played 60 cents sharp
+        let (spectrum, axis) = spectrum(&[detuned(67.0, 60.0)]);
+        let mut fixed = Chromagram::new().with_range(100.0, 5000.0).with_tuning(0.0);
+        assert_eq!(loudest(fixed.process_spectrum(&spectrum, axis)), "G#");
+        let mut sharp = Chromagram::new()
+            .with_range(100.0, 5000.0)
+            .with_tuning(60.0);
+        assert_eq!(loudest(sharp.process_spectrum(&spectrum, axis)), "G");
+    }
+
+    #[test]
+    fn filter_bank_bands_off_the_grid() {
+        // Semitone bank starting 25 cents above A0, as if f_min was slightly off
+        let frequencies: Vec<f32> = (0..88).map(|k| detuned(21.0 + k as f32, 25.0)).collect();
+        // Energy centred 0.2 semitones above the band of A4 (band 48)
+        let bands: Vec<f32> = (0..88)
+            .map(|k| (-(k as f32 - 48.2).powi(2) / 2.0).exp())
+            .collect();
+        let mut chromagram = Chromagram::new();
+        let chroma = chromagram.process_bands(&bands, &frequencies);
+        assert_eq!(loudest(chroma), "A");
+        assert!(
+            (chromagram.tuning() - 45.0).abs() < 2.0,
+            "{}",
+            chromagram.tuning()
+        );
+    }
+
+    #[test]
+    fn normalizations() {
+        let frequencies: Vec<f32> = (0..24).map(|k| detuned(48.0 + k as f32, 0.0)).collect();
+        let bands: Vec<f32> = (0..24)
+            .map(|k| if k % 12 < 3 { 1.0 } else { 0.5 })
+            .collect();
+        let chroma = |normalization| {
+            *Chromagram::new()
+                .with_tuning(0.0)
+                .with_normalization(normalization)
+                .process_bands(&bands, &frequencies)
+        };
+        assert_eq!(chroma(ChromaNormalization::None)[0], 2.0);
+        assert_eq!(chroma(ChromaNormalization::Max)[0], 1.0);
+        assert!((chroma(ChromaNormalization::Sum).iter().sum::<f32>() - 1.0).abs() < 1e-6);
+        let euclidean = chroma(ChromaNormalization::Euclidean);
+        assert!((euclidean.iter().map(|v| v * v).sum::<f32>() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_a_tuning_decay_above_one() {
+        let _ = Chromagram::new().with_tuning_decay(1.5);
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_a_nan_reference() {
+        let _ = Chromagram::new().with_reference(f32::NAN);
+    }
+}
@@ -0,0 +1,248 @@
+use std::{f32::consts::PI, sync::Arc};
+
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+
+use crate::window::{Window, WindowFunction};
+
+/// Spectral kernel values below this fraction of the kernel peak are dropped.
+const SPARSITY_THRESHOLD: f32 = 0.0054;
+
+/// Sparse spectral kernel of a single constant-Q bin.
+struct Kernel {
+    /// `(fft bin, conjugated kernel value)` pairs
+    values: Vec<(usize, Complex<f32>)>,
+}
+
+/// Constant-Q transform computed with precomputed sparse spectral kernels (Brown & Puckette).
+///
+/// Bins are geometrically spaced from `f_min` with `bins_per_octave` bins per octave,
+/// so with 12 bins per octave each bin is a semitone. Every bin has the same Q, so low bins
+/// use long analysis windows and high bins short ones, all ending at the newest sample.
+///
+/// Works on mono samples: [`ConstantQ::process`] keeps a sliding history of the last
+/// [`ConstantQ::fft_size`] samples, so it can be fed with chunks of any size.
+pub struct ConstantQ {
+    frequencies: Vec<f32>,
+    kernels: Vec<Kernel>,
+    fft: Arc<dyn Fft<f32>>,
+    history: Vec<f32>,
+    buffer: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+    /// Output of the last computed transform
+    spectrum: Vec<f32>,
+}
+
+impl ConstantQ {
+    /// Creates a transform covering `octaves` octaves above `f_min`.
+    ///
+    /// # Panics
+    ///
+    /// Like [`ConstantQ::with_bins`].
+    pub fn new(sample_rate: f32, f_min: f32, bins_per_octave: usize, octaves: usize) -> Self {
+        ConstantQ::with_bins(
+            sample_rate,
+            f_min,
+            bins_per_octave,
+            bins_per_octave * octaves,
+        )
+    }
+
+    /// Creates a transform with `num_bins` bins above `f_min`, e.g. 88 semitones for a piano layout.
+    ///
+    /// # Panics
+    ///
+    /// If `sample_rate` or `f_min` is not finite and positive, `bins_per_octave` is 0
+    /// or the highest bin is above the Nyquist frequency.
+    pub fn with_bins(
+        sample_rate: f32,
+        f_min: f32,
+        bins_per_octave: usize,
+        num_bins: usize,
+    ) -> Self {
+        if !(sample_rate.is_finite() && sample_rate > 0.0) {
+            panic!("sample rate ({}) must be finite and positive", sample_rate)
+        }
+        if !f_min.is_finite() || f_min <= 0.0 {
+            panic!("f_min ({}) must be finite and positive", f_min)
+        }
+        if bins_per_octave == 0 {
+            panic!("bins per octave ({}) must be at least 1", bins_per_octave)
+        }
+        let frequencies: Vec<f32> = (0..num_bins)
+            .map(|k| f_min * 2f32.powf(k as f32 / bins_per_octave as f32))
+            .collect();
+        if let Some(&f_max) = frequencies.last() {
+            if f_max >= sample_rate / 2.0 {
+                panic!(
+                    "highest bin ({} Hz) is above the Nyquist frequency ({} Hz)",
+                    f_max,
+                    sample_rate / 2.0
+                )
+            }
+        }
+
+        let q = 1.0 / (2f32.powf(1.0 / bins_per_octave as f32) - 1.0);
+        let longest = (q * sample_rate / f_min).ceil() as usize;
+        let fft_size = longest.next_power_of_two();
+        let fft = FftPlanner::<f32>::new().plan_fft_forward(fft_size);
+        let mut scratch = vec![Complex::new(0.0, 0.0); fft.get_inplace_scratch_len()];
+
+        let kernels = frequencies
+            .iter()
+            .map(|&f| {
+                let len = ((q * sample_rate / f).ceil() as usize).min(fft_size);
+                let window = Window::new(WindowFunction::Hann, len);
+                // Scaled so a full-scale sine at `f` reads 1.0
+                let gain = 2.0 / window.coefficients().iter().sum::<f32>();
+                let offset = fft_size - len;
+                let mut temporal = vec![Complex::new(0.0, 0.0); fft_size];
+                for (n, w) in window.coefficients().iter().enumerate() {
+                    let phase = 2.0 * PI * f * n as f32 / sample_rate;
+                    temporal[offset + n] = Complex::from_polar(w * gain, phase);
+                }
+                fft.process_with_scratch(&mut temporal, &mut scratch);
+                let peak = temporal.iter().map(|v| v.norm()).fold(0.0, f32::max);
+                let values = temporal
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, v)| v.norm() >= peak * SPARSITY_THRESHOLD)
+                    .map(|(j, v)| (j, v.conj() / fft_size as f32))
+                    .collect();
+                Kernel { values }
+            })
+            .collect();
+
+        ConstantQ {
+            spectrum: vec![0.0; num_bins],
+            frequencies,
+            kernels,
+            fft,
+            history: vec![0.0; fft_size],
+            buffer: vec![Complex::new(0.0, 0.0); fft_size],
+            scratch,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.frequencies.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frequencies.is_empty()
+    }
+
+    /// Number of past samples the lowest bin looks at.
+    pub fn fft_size(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Centre frequency of each bin in Hz.
+    pub fn frequencies(&self) -> &[f32] {
+        &self.frequencies
+    }
+
+    /// Appends `samples` to the history and returns the magnitude of each bin.
+    ///
+    /// A full-scale sine at a bin frequency reads 1.0. The slice is overwritten by the next call.
+    pub fn process(&mut self, samples: &[f32]) -> &[f32] {
+        let samples = &samples[samples.len().saturating_sub(self.history.len())..];
+        self.history.rotate_left(samples.len());
+        let start = self.history.len() - samples.len();
+        self.history[start..].copy_from_slice(samples);
+
+        for (value, &sample) in self.buffer.iter_mut().zip(self.history.iter()) {
+            *value = Complex::new(sample, 0.0);
+        }
+        self.fft
+            .process_with_scratch(&mut self.buffer, &mut self.scratch);
+
+        for (magnitude, kernel) in self.spectrum.iter_mut().zip(self.kernels.iter()) {
+            let value: Complex<f32> = kernel.values.iter().map(|&(j, k)| self.buffer[j] * k).sum();
+            *magnitude = value.norm();
+        }
+        &self.spectrum
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::sine;
+
+    const SAMPLE_RATE: f32 = 8000.0;
+
+    #[test]
+    fn semitone_layout_matches_the_bandpass_filter_bank() {
+        let cqt = ConstantQ::with_bins(48000.0, 27.5, 12, 88);
+        assert_eq!(cqt.len(), 88);
+        let mut f = 27.5f32;
+        for &hz in cqt.frequencies() {
+            assert!((hz - f).abs() / f < 1e-4, "{} != {}", hz, f);
+            f *= 2f32.powf(1.0 / 12.0);
+        }
+        assert!((cqt.frequencies()[87] - 4186.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn full_scale_sine_reads_one_at_its_bin() {
+        let mut cqt = ConstantQ::new(SAMPLE_RATE, 110.0, 12, 3);
+        for target in [0, 12, 19, 35] {
+            let hz = cqt.frequencies()[target];
+            let samples = sine(hz, SAMPLE_RATE, cqt.fft_size());
+            let res = cqt.process(&samples);
+            let loudest = res
+                .iter()
+                .enumerate()
+                .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
+                .unwrap()
+                .0;
+            assert_eq!(loudest, target);
+            assert!((res[target] - 1.0).abs() < 0.02, "{}", res[target]);
+            // With a Hann window the neighbouring bin is at about -6 dB
+            // and the first null falls two bins away
+            if target > 1 {
+                assert!(res[target - 1] < 0.6, "{}", res[target - 1]);
+                assert!(res[target - 2] < 0.05, "{}", res[target - 2]);
+            }
+        }
+    }
+
+    #[test]
+    fn history_slides_across_chunks() {
+        let mut chunked = ConstantQ::new(SAMPLE_RATE, 110.0, 12, 3);
+        let mut whole = ConstantQ::new(SAMPLE_RATE, 110.0, 12, 3);
+        let samples = sine(330.0, SAMPLE_RATE, 3000);
+        for chunk in samples.chunks(256) {
+            chunked.process(chunk);
+        }
+        let expected = whole.process(&samples).to_vec();
+        let res = chunked.process(&[]);
+        for (a, b) in res.iter().zip(expected.iter()) {
+            assert!((a - b).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn f_min_must_be_positive() {
+        let _ = ConstantQ::new(SAMPLE_RATE, 0.0, 12, 3);
+    }
+
+    #[test]
+    #[should_panic]
+    fn f_min_must_be_finite() {
+        let _ = ConstantQ::new(SAMPLE_RATE, f32::NAN, 12, 3);
+    }
+
+    #[test]
+    #[should_panic]
+    fn sample_rate_must_be_finite_and_positive() {
+        let _ = ConstantQ::new(f32::NAN, 110.0, 12, 3);
+    }
+
+    #[test]
+    #[should_panic]
+    fn bins_per_octave_must_not_be_zero() {
+        let _ = ConstantQ::with_bins(SAMPLE_RATE, 110.0, 0, 12);
+    }
+}
@@ -0,0 +1,283 @@
+use crate::FrequencyAxis;
+
+/// Keeps the logarithm in [`flatness`] finite for empty bins.
+const LOG_FLOOR: f32 = 1e-10;
+
+/// Magnitude weighted centre of mass of the spectrum in Hz.
+pub fn centroid(spectrum: &[f32], axis: FrequencyAxis) -> f32 {
+    Moments::new(spectrum, axis).centroid
+}
+
+/// Standard deviation of the spectrum around its centroid in Hz.
+pub fn spread(spectrum: &[f32], axis: FrequencyAxis) -> f32 {
+    Moments::new(spectrum, axis).spread
+}
+
+/// Asymmetry of the spectrum around its centroid, positive when the energy leans
+/// towards low frequencies with a tail above.
+pub fn skewness(spectrum: &[f32], axis: FrequencyAxis) -> f32 {
+    Moments::new(spectrum, axis).skewness
+}
+
+/// Peakedness of the spectrum around its centroid, 3 for a Gaussian shape.
+pub fn kurtosis(spectrum: &[f32], axis: FrequencyAxis) -> f32 {
+    Moments::new(spectrum, axis).kurtosis
+}
+
+/// Frequency in Hz below which `percent` of the spectral energy lies.
+///
+/// # Panics
+///
+/// If `percent` is not in `0.0..=100.0`.
+pub fn rolloff(spectrum: &[f32], axis: FrequencyAxis, percent: f32) -> f32 {
+    if !(0.0..=100.0).contains(&percent) {
+        panic!("rolloff percent ({}) must be in 0..=100", percent)
+    }
+    let total: f32 = spectrum.iter().map(|m| m * m).sum();
+    if total <= 0.0 {
+        return 0.0;
+    }
+    let target = total * percent / 100.0;
+    let mut energy = 0.0;
+    for (i, m) in spectrum.iter().enumerate() {
+        energy += m * m;
+        if energy >= target {
+            return axis.bin_frequency(i);
+        }
+    }
+    axis.bin_frequency(spectrum.len() - 1)
+}
+
+/// Ratio of the geometric to the arithmetic mean (Wiener entropy).
+///
+/// 1 for a flat, noise-like spectrum and close to 0 for a tonal one.
+pub fn flatness(spectrum: &[f32]) -> f32 {
+    let mean = spectrum.iter().sum::<f32>() / spectrum.len().max(1) as f32;
+    if mean <= 0.0 {
+        return 0.0;
+    }
+    let log_mean =
+        spectrum.iter().map(|m| m.max(LOG_FLOOR).ln()).sum::<f32>() / spectrum.len() as f32;
+    (log_mean.exp() / mean).min(1.0)
+}
+
+/// Ratio of the highest magnitude to the mean magnitude.
+pub fn crest(spectrum: &[f32]) -> f32 {
+    let mean = spectrum.iter().sum::<f32>() / spectrum.len().max(1) as f32;
+    if mean <= 0.0 {
+        return 0.0;
+    }
+    spectrum.iter().fold(0.0, |max: f32, &m| max.max(m)) / mean
+}
+
+/// Sum of the magnitude increases from `previous` to `current`.
+///
+/// Decreases are ignored, so the flux rises on onsets and not on decays.
+///
+/// # Panics
+///
+/// If `previous` length is different than `current` length.
+pub fn flux(previous: &[f32], current: &[f32]) -> f32 {
+    if previous.len() != current.len() {
+        panic!(
+            "previous len ({}) is different than current len ({})",
+            previous.len(),
+            current.len()
+        )
+    }
+    previous
+        .iter()
+        .zip(current.iter())
+        .map(|(p, c)| (c - p).max(0.0))
+        .sum()
+}
+
+/// Centroid and standardized moments of a spectrum.
+struct Moments {
+    centroid: f32,
+    spread: f32,
+    skewness: f32,
+    kurtosis: f32,
+}
+
+impl Moments {
+    fn new(spectrum: &[f32], axis: FrequencyAxis) -> Self {
+        let mut moments = Moments {
+            centroid: 0.0,
+            spread: 0.0,
+            skewness: 0.0,
+            kurtosis: 0.0,
+        };
+        let total: f32 = spectrum.iter().sum();
+        if total <= 0.0 {
+            return moments;
+        }
+        let weighted = |power: i32, center: f32| {
+            spectrum
+                .iter()
+                .enumerate()
+                .map(|(i, m)| (axis.bin_frequency(i) - center).powi(power) * m)
+                .sum::<f32>()
+                / total
+        };
+        moments.centroid = weighted(1, 0.0);
+        moments.spread = weighted(2, moments.centroid).sqrt();
+        if moments.spread > 0.0 {
+            moments.skewness = weighted(3, moments.centroid) / moments.spread.powi(3);
+            moments.kurtosis = weighted(4, moments.centroid) / moments.spread.powi(4);
+        }
+        moments
+    }
+}
+
+/// Every descriptor of a single frame.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SpectralDescriptors {
+    /// Hz
+    pub centroid: f32,
+    /// Hz
+    pub spread: f32,
+    pub skewness: f32,
+    pub kurtosis: f32,
+    /// Hz
+    pub rolloff: f32,
+    pub flatness: f32,
+    pub crest: f32,
+    /// Flux from the previous frame, 0 for the first one
+    pub flux: f32,
+}
+
+/// Computes [`SpectralDescriptors`] of consecutive frames, keeping the previous
+/// spectrum for the flux.
+///
+/// Spectra hold linear magnitudes laid out like [`crate::FrequencySpectrum::frequency_spectrum`],
+/// `axis` maps their indices to Hz. Descriptors of a silent spectrum are 0.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Descriptors {
+    rolloff_percent: f32,
+    previous: Vec<f32>,
+}
+
+impl Default for Descriptors {
+    fn default() -> Self {
+        Descriptors::new()
+    }
+}
+
+impl Descriptors {
+    /// Computes the rolloff at 85% of the energy.
+    pub fn new() -> Self {
+        Descriptors {
+            rolloff_percent: 85.0,
+            previous: Vec::new(),
+        }
+    }
+
+    /// # Panics
+    ///
+    /// If `percent` is not in `0.0..=100.0`.
+    pub fn with_rolloff(mut self, percent: f32) -> Self {
+        if !(0.0..=100.0).contains(&percent) {
+            panic!("rolloff percent ({}) must be in 0..=100", percent)
+        }
+        self.rolloff_percent = percent;
+        self
+    }
+
+    pub fn rolloff_percent(&self) -> f32 {
+        self.rolloff_percent
+    }
+
+    /// Computes the descriptors of `spectrum` and keeps it for the next flux.
+    pub fn compute(&mut self, spectrum: &[f32], axis: FrequencyAxis) -> SpectralDescriptors {
+        let moments = Moments::new(spectrum, axis);
+        let flux = if self.previous.len() == spectrum.len() {
+            flux(&self.previous, spectrum)
+        } else {
+            0.0
+        };
+        self.previous.clear();
+        self.previous.extend_from_slice(spectrum);
+        SpectralDescriptors {
+            centroid: moments.centroid,
+            spread: moments.spread,
+            skewness: moments.skewness,
+            kurtosis: moments.kurtosis,
+            rolloff: rolloff(spectrum, axis, self.rolloff_percent),
+            flatness: flatness(spectrum),
+            crest: crest(spectrum),
+            flux,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bin `i` is at `(i + 1) * 10` Hz.
+    fn axis() -> FrequencyAxis {
+        FrequencyAxis::new(160.0, 16)
+    }
+
+    #[test]
+    fn single_bin_has_its_frequency_and_no_spread() {
+        let mut spectrum = [0.0; 8];
+        spectrum[3] = 2.0;
+        assert_eq!(centroid(&spectrum, axis()), 40.0);
+        assert_eq!(spread(&spectrum, axis()), 0.0);
+        assert_eq!(rolloff(&spectrum, axis(), 85.0), 40.0);
+        assert_eq!(crest(&spectrum), 8.0);
+        assert!(flatness(&spectrum) < 1e-6);
+    }
+
+    #[test]
+    fn symmetric_pair_has_no_skewness() {
+        let mut spectrum = [0.0; 8];
+        spectrum[1] = 1.0;
+        spectrum[5] = 1.0;
+        assert_eq!(centroid(&spectrum, axis()), 40.0);
+        assert_eq!(spread(&spectrum, axis()), 20.0);
+        assert_eq!(skewness(&spectrum, axis()), 0.0);
+        assert_eq!(kurtosis(&spectrum, axis()), 1.0);
+    }
+
+    #[test]
+    fn low_heavy_spectrum_has_positive_skewness() {
+        let spectrum = [4.0, 3.0, 1.0, 0.5, 0.25, 0.1, 0.1, 0.1];
+        assert!(skewness(&spectrum, axis()) > 0.0);
+    }
+
+    #[test]
+    fn flat_spectrum_is_flat() {
+        let spectrum = [0.5; 8];
+        assert!((flatness(&spectrum) - 1.0).abs() < 1e-6);
+        assert_eq!(crest(&spectrum), 1.0);
+        assert_eq!(centroid(&spectrum, axis()), 45.0);
+        assert_eq!(rolloff(&spectrum, axis(), 50.0), 40.0);
+        assert_eq!(rolloff(&spectrum, axis(), 100.0), 80.0);
+    }
+
+    #[test]
+    fn silence_has_zero_descriptors() {
+        let spectrum = [0.0; 8];
+        let descriptors = Descriptors::new().compute(&spectrum, axis());
+        assert_eq!(descriptors, SpectralDescriptors::default());
+    }
+
+    #[test]
+    fn flux_only_counts_increases() {
+        assert_eq!(flux(&[1.0, 2.0, 3.0], &[2.0, 0.0, 3.5]), 1.5);
+
+        let mut descriptors = Descriptors::new();
+        assert_eq!(descriptors.compute(&[1.0, 1.0], axis()).flux, 0.0);
+        assert_eq!(descriptors.compute(&[3.0, 0.0], axis()).flux, 2.0);
+        assert_eq!(descriptors.compute(&[3.0, 0.0], axis()).flux, 0.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn rolloff_percent_must_be_a_percentage() {
+        let _ = Descriptors::new().with_rolloff(150.0);
+    }
+}
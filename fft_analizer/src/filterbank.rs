@@ -0,0 +1,333 @@
+/// Mel scale variant used by [`Filterbank::mel`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MelScale {
+    /// `2595 * log10(1 + f / 700)`, filters peak at 1.
+    #[default]
+    Htk,
+    /// Linear below 1 kHz and logarithmic above, filters are normalized to unit area.
+    Slaney,
+}
+
+impl MelScale {
+    pub fn hz_to_mel(&self, hz: f32) -> f32 {
+        match self {
+            MelScale::Htk => 2595.0 * (1.0 + hz / 700.0).log10(),
+            MelScale::Slaney => {
+                if hz < SLANEY_MIN_LOG_HZ {
+                    hz / SLANEY_LINEAR_STEP
+                } else {
+                    SLANEY_MIN_LOG_MEL + (hz / SLANEY_MIN_LOG_HZ).ln() / SLANEY_LOG_STEP
+                }
+            }
+        }
+    }
+
+    pub fn mel_to_hz(&self, mel: f32) -> f32 {
+        match self {
+            MelScale::Htk => 700.0 * (10f32.powf(mel / 2595.0) - 1.0),
+            MelScale::Slaney => {
+                if mel < SLANEY_MIN_LOG_MEL {
+                    mel * SLANEY_LINEAR_STEP
+                } else {
+                    SLANEY_MIN_LOG_HZ * (SLANEY_LOG_STEP * (mel - SLANEY_MIN_LOG_MEL)).exp()
+                }
+            }
+        }
+    }
+}
+
+const SLANEY_LINEAR_STEP: f32 = 200.0 / 3.0;
+const SLANEY_MIN_LOG_HZ: f32 = 1000.0;
+const SLANEY_MIN_LOG_MEL: f32 = SLANEY_MIN_LOG_HZ / SLANEY_LINEAR_STEP;
+/// `ln(6.4) / 27`
+const SLANEY_LOG_STEP: f32 = 0.068_751_78;
+
+/// Traunmüller's approximation of the Bark scale.
+pub fn hz_to_bark(hz: f32) -> f32 {
+    26.81 * hz / (1960.0 + hz) - 0.53
+}
+
+pub fn bark_to_hz(bark: f32) -> f32 {
+    1960.0 * (bark + 0.53) / (26.28 - bark)
+}
+
+/// Glasberg and Moore's ERB-rate scale (number of ERBs below `hz`).
+pub fn hz_to_erb(hz: f32) -> f32 {
+    21.4 * (1.0 + 0.00437 * hz).log10()
+}
+
+pub fn erb_to_hz(erb: f32) -> f32 {
+    (10f32.powf(erb / 21.4) - 1.0) / 0.00437
+}
+
+/// A filter stored as its non-zero weights over the spectrum.
+#[derive(Debug, Clone, PartialEq)]
+struct Filter {
+    /// Spectrum index of the first weight
+    start: usize,
+    weights: Vec<f32>,
+}
+
+/// Triangular filters projecting the output of [`crate::FrequencySpectrum::frequency_spectrum`]
+/// onto a perceptual frequency scale.
+///
+/// The filter matrix is computed once when the filterbank is built and reused every frame.
+/// Spectrum index `i` is the bin at `(i + 1) * sample_rate / fft_size` Hz since the DC is skipped,
+/// where `fft_size` includes any zero padding (see [`crate::FrequencySpectrum::fft_size`]).
+pub struct Filterbank {
+    filters: Vec<Filter>,
+    frequencies: Vec<f32>,
+}
+
+impl Filterbank {
+    /// Triangular mel filters between `f_min` and `f_max` Hz.
+    pub fn mel(
+        scale: MelScale,
+        sample_rate: f32,
+        fft_size: usize,
+        num_filters: usize,
+        f_min: f32,
+        f_max: f32,
+    ) -> Self {
+        Filterbank::triangular(
+            sample_rate,
+            fft_size,
+            num_filters,
+            f_min,
+            f_max,
+            |hz| scale.hz_to_mel(hz),
+            |mel| scale.mel_to_hz(mel),
+            scale == MelScale::Slaney,
+        )
+    }
+
+    /// Triangular filters equally spaced on the Bark scale between `f_min` and `f_max` Hz.
+    pub fn bark(
+        sample_rate: f32,
+        fft_size: usize,
+        num_filters: usize,
+        f_min: f32,
+        f_max: f32,
+    ) -> Self {
+        Filterbank::triangular(
+            sample_rate,
+            fft_size,
+            num_filters,
+            f_min,
+            f_max,
+            hz_to_bark,
+            bark_to_hz,
+            false,
+        )
+    }
+
+    /// Triangular filters equally spaced on the ERB-rate scale between `f_min` and `f_max` Hz.
+    pub fn erb(
+        sample_rate: f32,
+        fft_size: usize,
+        num_filters: usize,
+        f_min: f32,
+        f_max: f32,
+    ) -> Self {
+        Filterbank::triangular(
+            sample_rate,
+            fft_size,
+            num_filters,
+            f_min,
+            f_max,
+            hz_to_erb,
+            erb_to_hz,
+            false,
+        )
+    }
+
+    /// Builds `num_filters` triangles whose edges are equally spaced on a warped scale.
+    ///
+    /// Each triangle starts at the centre of the previous one and ends at the centre of the next.
+    /// With `unit_area` the triangles are scaled to `2 / (high - low)` instead of peaking at 1.
+    #[allow(clippy::too_many_arguments)]
+    fn triangular(
+        sample_rate: f32,
+        fft_size: usize,
+        num_filters: usize,
+        f_min: f32,
+        f_max: f32,
+        to_scale: impl Fn(f32) -> f32,
+        from_scale: impl Fn(f32) -> f32,
+        unit_area: bool,
+    ) -> Self {
+        let bin_width = sample_rate / fft_size as f32;
+        let spectrum_len = fft_size / 2;
+        let (low, high) = (to_scale(f_min), to_scale(f_max));
+        let edges: Vec<f32> = (0..num_filters + 2)
+            .map(|i| from_scale(low + (high - low) * i as f32 / (num_filters + 1) as f32))
+            .collect();
+
+        let mut filters = Vec::with_capacity(num_filters);
+        for triangle in edges.windows(3) {
+            let (left, center, right) = (triangle[0], triangle[1], triangle[2]);
+            let height = if unit_area { 2.0 / (right - left) } else { 1.0 };
+            let mut start = None;
+            let mut weights = Vec::new();
+            for i in 0..spectrum_len {
+                let hz = (i + 1) as f32 * bin_width;
+                let weight = if hz > left && hz <= center {
+                    (hz - left) / (center - left)
+                } else if hz > center && hz < right {
+                    (right - hz) / (right - center)
+                } else {
+                    0.0
+                };
+                if weight > 0.0 {
+                    start.get_or_insert(i);
+                    weights.push(weight * height);
+                } else if start.is_some() {
+                    break;
+                }
+            }
+            filters.push(Filter {
+                start: start.unwrap_or(0),
+                weights,
+            });
+        }
+
+        Filterbank {
+            filters,
+            frequencies: edges[1..=num_filters].to_vec(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.filters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    /// Centre frequency of each filter in Hz.
+    pub fn frequencies(&self) -> &[f32] {
+        &self.frequencies
+    }
+
+    /// Weights of filter `index` and the spectrum index of the first one.
+    pub fn weights(&self, index: usize) -> (usize, &[f32]) {
+        let filter = &self.filters[index];
+        (filter.start, &filter.weights)
+    }
+
+    /// Projects `spectrum` onto the filters and writes one value per filter into `out`.
+    ///
+    /// Filters narrower than a bin hold no weight and always output 0.
+    ///
+    /// # Panics
+    ///
+    /// If `out` length is different than [`Filterbank::len`].
+    pub fn apply(&self, spectrum: &[f32], out: &mut [f32]) {
+        if out.len() != self.filters.len() {
+            panic!(
+                "out len ({}) is different than filters len ({})",
+                out.len(),
+                self.filters.len()
+            )
+        }
+        for (value, filter) in out.iter_mut().zip(self.filters.iter()) {
+            *value = spectrum
+                .iter()
+                .skip(filter.start)
+                .zip(filter.weights.iter())
+                .map(|(s, w)| s * w)
+                .sum();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::assert_close;
+
+    const SAMPLE_RATE: f32 = 16000.0;
+    const FFT_SIZE: usize = 512;
+
+    #[test]
+    fn scale_conversions_match_known_values() {
+        assert_close(MelScale::Htk.hz_to_mel(1000.0), 1000.0, 0.1);
+        assert_close(MelScale::Slaney.hz_to_mel(1000.0), 15.0, 1e-4);
+        assert_close(MelScale::Slaney.hz_to_mel(6400.0), 42.0, 1e-3);
+        assert_close(hz_to_bark(1000.0), 8.53, 0.01);
+        assert_close(hz_to_erb(1000.0), 15.62, 0.01);
+        for hz in [50.0, 440.0, 1000.0, 4000.0, 7999.0] {
+            assert_close(
+                MelScale::Htk.mel_to_hz(MelScale::Htk.hz_to_mel(hz)),
+                hz,
+                0.05,
+            );
+            assert_close(
+                MelScale::Slaney.mel_to_hz(MelScale::Slaney.hz_to_mel(hz)),
+                hz,
+                0.05,
+            );
+            assert_close(bark_to_hz(hz_to_bark(hz)), hz, 0.05);
+            assert_close(erb_to_hz(hz_to_erb(hz)), hz, 0.05);
+        }
+    }
+
+    #[test]
+    fn htk_filters_peak_at_one() {
+        let bank = Filterbank::mel(MelScale::Htk, SAMPLE_RATE, FFT_SIZE, 26, 0.0, 8000.0);
+        assert_eq!(bank.len(), 26);
+        for i in 0..bank.len() {
+            let (_, weights) = bank.weights(i);
+            let max = weights.iter().cloned().fold(0.0, f32::max);
+            assert!(max <= 1.0 && max > 0.5, "filter {} peaks at {}", i, max);
+        }
+    }
+
+    #[test]
+    fn slaney_filters_have_unit_area() {
+        let bank = Filterbank::mel(MelScale::Slaney, SAMPLE_RATE, 8192, 40, 0.0, 8000.0);
+        let bin_width = SAMPLE_RATE / 8192.0;
+        for i in 0..bank.len() {
+            let (_, weights) = bank.weights(i);
+            let area: f32 = weights.iter().sum::<f32>() * bin_width;
+            assert_close(area, 1.0, 0.02);
+        }
+    }
+
+    #[test]
+    fn centre_frequencies_are_equally_spaced_on_their_scale() {
+        let bank = Filterbank::bark(SAMPLE_RATE, FFT_SIZE, 20, 20.0, 7000.0);
+        let barks: Vec<f32> = bank.frequencies().iter().map(|&f| hz_to_bark(f)).collect();
+        let step = barks[1] - barks[0];
+        for pair in barks.windows(2) {
+            assert_close(pair[1] - pair[0], step, 1e-3);
+        }
+
+        let bank = Filterbank::erb(SAMPLE_RATE, FFT_SIZE, 32, 50.0, 8000.0);
+        let erbs: Vec<f32> = bank.frequencies().iter().map(|&f| hz_to_erb(f)).collect();
+        let step = erbs[1] - erbs[0];
+        for pair in erbs.windows(2) {
+            assert_close(pair[1] - pair[0], step, 1e-3);
+        }
+    }
+
+    #[test]
+    fn a_tone_excites_the_filter_centred_on_it() {
+        let bank = Filterbank::mel(MelScale::Htk, SAMPLE_RATE, FFT_SIZE, 26, 0.0, 8000.0);
+        let bin_width = SAMPLE_RATE / FFT_SIZE as f32;
+        let mut spectrum = vec![0.0; FFT_SIZE / 2];
+        let target = 10;
+        let index = (bank.frequencies()[target] / bin_width).round() as usize - 1;
+        spectrum[index] = 1.0;
+        let mut out = vec![0.0; bank.len()];
+        bank.apply(&spectrum, &mut out);
+        let loudest = out
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
+            .unwrap()
+            .0;
+        assert_eq!(loudest, target);
+    }
+}
@@ -0,0 +1,131 @@
+/// Sliding frames over a sample stream.
+///
+/// Keeps the last `frame_len` samples in a circular history. The first frame is complete
+/// once `frame_len` samples arrived, then one every `hop` samples, so consecutive frames
+/// overlap by `frame_len - hop` samples.
+#[derive(Debug, Clone)]
+pub struct Framer {
+    /// Circular history of the last samples, the oldest one is at `index`
+    history: Vec<f32>,
+    index: usize,
+    /// Samples written to the history, up to `frame_len`
+    filled: usize,
+    /// Samples received since the last frame
+    fresh: usize,
+    hop: usize,
+    /// History of the last complete frame in chronological order
+    frame: Vec<f32>,
+}
+
+impl Framer {
+    /// # Panics
+    ///
+    /// If `hop` is 0 or greater than `frame_len`.
+    pub fn new(frame_len: usize, hop: usize) -> Self {
+        check_hop(hop, frame_len);
+        Framer {
+            history: vec![0.0; frame_len],
+            index: 0,
+            filled: 0,
+            fresh: 0,
+            hop,
+            frame: vec![0.0; frame_len],
+        }
+    }
+
+    pub fn frame_len(&self) -> usize {
+        self.history.len()
+    }
+
+    pub fn hop(&self) -> usize {
+        self.hop
+    }
+
+    /// Changes the hop, keeping the history.
+    ///
+    /// # Panics
+    ///
+    /// If `hop` is 0 or greater than the frame length.
+    pub fn set_hop(&mut self, hop: usize) {
+        check_hop(hop, self.history.len());
+        self.hop = hop;
+    }
+
+    /// Circular history of the last `frame_len` samples, see [`Framer::index`].
+    pub fn history(&self) -> &[f32] {
+        &self.history
+    }
+
+    /// Position of the oldest sample in [`Framer::history`].
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Last complete frame, oldest sample first.
+    pub fn frame(&self) -> &[f32] {
+        &self.frame
+    }
+
+    /// Adds a sample and returns whether it completed a frame.
+    pub fn push(&mut self, sample: f32) -> bool {
+        let len = self.history.len();
+        self.history[self.index] = sample;
+        self.index = (self.index + 1) % len;
+        self.filled = (self.filled + 1).min(len);
+        self.fresh += 1;
+        if self.filled < len || self.fresh < self.hop {
+            return false;
+        }
+        self.fresh = 0;
+        let (newest, oldest) = self.history.split_at(self.index);
+        self.frame[..oldest.len()].copy_from_slice(oldest);
+        self.frame[oldest.len()..].copy_from_slice(newest);
+        true
+    }
+}
+
+fn check_hop(hop: usize, frame_len: usize) {
+    if hop == 0 || hop > frame_len {
+        panic!("hop ({}) must be in 1..={}", hop, frame_len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_overlap_by_frame_len_minus_hop() {
+        let mut framer = Framer::new(4, 2);
+        let mut frames = Vec::new();
+        for sample in 0..9 {
+            if framer.push(sample as f32) {
+                frames.push(framer.frame().to_vec());
+            }
+        }
+        assert_eq!(
+            frames,
+            [
+                vec![0.0, 1.0, 2.0, 3.0],
+                vec![2.0, 3.0, 4.0, 5.0],
+                vec![4.0, 5.0, 6.0, 7.0],
+            ]
+        );
+        assert_eq!(framer.history()[framer.index()], 5.0);
+    }
+
+    #[test]
+    fn hop_changes_keep_the_history() {
+        let mut framer = Framer::new(4, 4);
+        assert!((0..4).map(|s| framer.push(s as f32)).last().unwrap());
+        framer.set_hop(1);
+        assert!(framer.push(4.0));
+        assert_eq!(framer.frame(), [1.0, 2.0, 3.0, 4.0]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn hop_cannot_exceed_the_frame() {
+        let _ = Framer::new(4, 5);
+    }
+}
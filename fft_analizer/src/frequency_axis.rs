@@ -0,0 +1,89 @@
+/// Maps indices of the [`crate::FrequencySpectrum`] output to frequencies.
+///
+/// The spectrum skips the DC bin, so index `i` is FFT bin `i + 1`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrequencyAxis {
+    sample_rate: f32,
+    fft_size: usize,
+}
+
+impl FrequencyAxis {
+    /// `fft_size` includes any zero padding.
+    pub fn new(sample_rate: f32, fft_size: usize) -> Self {
+        FrequencyAxis {
+            sample_rate,
+            fft_size,
+        }
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    pub fn fft_size(&self) -> usize {
+        self.fft_size
+    }
+
+    /// Number of spectrum indices, from the first bin above DC up to Nyquist.
+    pub fn len(&self) -> usize {
+        self.fft_size / 2
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Distance between two bins in Hz.
+    pub fn bin_width(&self) -> f32 {
+        self.sample_rate / self.fft_size as f32
+    }
+
+    /// Centre frequency in Hz of spectrum index `index`.
+    pub fn bin_frequency(&self, index: usize) -> f32 {
+        (index + 1) as f32 * self.bin_width()
+    }
+
+    /// Spectrum index of the bin nearest to `hz`.
+    ///
+    /// Returns `None` when the nearest bin is the skipped DC bin or is above Nyquist.
+    pub fn frequency_to_bin(&self, hz: f32) -> Option<usize> {
+        let bin = (hz / self.bin_width()).round();
+        if bin < 1.0 || bin > self.len() as f32 {
+            None
+        } else {
+            Some(bin as usize - 1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indices_are_offset_by_the_skipped_dc_bin() {
+        let axis = FrequencyAxis::new(48000.0, 1024);
+        assert_eq!(axis.bin_width(), 46.875);
+        assert_eq!(axis.bin_frequency(0), 46.875);
+        assert_eq!(axis.bin_frequency(axis.len() - 1), 24000.0);
+        assert_eq!(axis.frequency_to_bin(46.875), Some(0));
+        assert_eq!(axis.frequency_to_bin(24000.0), Some(511));
+    }
+
+    #[test]
+    fn frequency_to_bin_inverts_bin_frequency() {
+        let axis = FrequencyAxis::new(44100.0, 2048);
+        for i in 0..axis.len() {
+            assert_eq!(axis.frequency_to_bin(axis.bin_frequency(i)), Some(i));
+        }
+    }
+
+    #[test]
+    fn frequencies_outside_the_spectrum_have_no_bin() {
+        let axis = FrequencyAxis::new(48000.0, 1024);
+        assert_eq!(axis.frequency_to_bin(0.0), None);
+        assert_eq!(axis.frequency_to_bin(20.0), None);
+        assert_eq!(axis.frequency_to_bin(30.0), Some(0));
+        assert_eq!(axis.frequency_to_bin(24100.0), None);
+    }
+}
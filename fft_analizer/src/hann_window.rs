@@ -1,27 +1,24 @@
+//! Kept for compatibility, use [`crate::window::Window`] with [`WindowFunction::Hann`].
+
+use crate::window::{Window, WindowFunction};
+
+#[deprecated(note = "use `window::Window::new(WindowFunction::Hann, n)`")]
 pub struct HannWindow {
-    window: Vec<f32>,
+    window: Window,
 }
+
+#[allow(deprecated)]
 impl HannWindow {
     pub fn new(n: usize) -> Self {
-        let mut window = Vec::with_capacity(n);
-        for i in 0..n {
-            let value =
-                0.5 * (1.0 - (2.0 * std::f32::consts::PI * i as f32 / (n as f32 - 1.0)).cos());
-            window.push(value);
+        HannWindow {
+            window: Window::new(WindowFunction::Hann, n),
         }
-        HannWindow { window }
     }
 
+    /// # Panics
+    ///
+    /// If `samples` length is different than the window length.
     pub fn apply(&self, samples: &mut [f32]) {
-        if samples.len() != self.window.len() {
-            panic!(
-                "samples len ({}) is different than window len ({})",
-                samples.len(),
-                self.window.len()
-            )
-        }
-        for (i, sample) in samples.iter_mut().enumerate() {
-            *sample *= self.window[i];
-        }
+        self.window.apply(samples);
     }
 }
@@ -0,0 +1,174 @@
+use std::{collections::VecDeque, fmt};
+
+use crate::chroma::PITCH_CLASSES;
+
+/// Krumhansl–Kessler probe-tone profile of major keys, starting at the tonic.
+const MAJOR_PROFILE: [f32; 12] = [
+    6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88,
+];
+/// Krumhansl–Kessler probe-tone profile of minor keys, starting at the tonic.
+const MINOR_PROFILE: [f32; 12] = [
+    6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Major,
+    Minor,
+}
+
+/// A musical key.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Key {
+    /// Pitch class of the tonic, 0 is C
+    pub tonic: usize,
+    pub mode: Mode,
+    /// Correlation of the chroma with the key profile, from -1 to 1
+    pub correlation: f32,
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = match self.mode {
+            Mode::Major => "major",
+            Mode::Minor => "minor",
+        };
+        write!(f, "{} {}", PITCH_CLASSES[self.tonic], mode)
+    }
+}
+
+/// Finds the key whose Krumhansl–Schmuckler profile best correlates with `chroma`.
+///
+/// Returns `None` for a flat or silent chroma.
+pub fn detect_key(chroma: &[f32; 12]) -> Option<Key> {
+    let mut best: Option<Key> = None;
+    for (mode, profile) in [(Mode::Major, &MAJOR_PROFILE), (Mode::Minor, &MINOR_PROFILE)] {
+        for tonic in 0..12 {
+            let correlation = correlation(chroma, profile, tonic)?;
+            if best.is_none_or(|key| correlation > key.correlation) {
+                best = Some(Key {
+                    tonic,
+                    mode,
+                    correlation,
+                });
+            }
+        }
+    }
+    best
+}
+
+/// Pearson correlation between `chroma` and `profile` rotated to start at `tonic`.
+fn correlation(chroma: &[f32; 12], profile: &[f32; 12], tonic: usize) -> Option<f32> {
+    let mean = |values: &[f32; 12]| values.iter().sum::<f32>() / 12.0;
+    let (chroma_mean, profile_mean) = (mean(chroma), mean(profile));
+    let (mut covariance, mut chroma_var, mut profile_var) = (0.0, 0.0, 0.0);
+    for (class, value) in chroma.iter().enumerate() {
+        let c = value - chroma_mean;
+        let p = profile[(class + 12 - tonic) % 12] - profile_mean;
+        covariance += c * p;
+        chroma_var += c * c;
+        profile_var += p * p;
+    }
+    if chroma_var <= 0.0 {
+        return None;
+    }
+    Some(covariance / (chroma_var * profile_var).sqrt())
+}
+
+/// Key detection over the chroma of the last frames.
+#[derive(Debug, Clone)]
+pub struct KeyDetector {
+    window: usize,
+    history: VecDeque<[f32; 12]>,
+    key: Option<Key>,
+}
+
+impl KeyDetector {
+    /// Detects the key of the sum of the last `window` chroma frames.
+    ///
+    /// # Panics
+    ///
+    /// If `window` is 0.
+    pub fn new(window: usize) -> Self {
+        if window == 0 {
+            panic!("key window must hold at least one frame")
+        }
+        KeyDetector {
+            window,
+            history: VecDeque::with_capacity(window),
+            key: None,
+        }
+    }
+
+    pub fn window(&self) -> usize {
+        self.window
+    }
+
+    /// Key of the frames pushed so far, `None` until a non silent frame is pushed.
+    pub fn key(&self) -> Option<Key> {
+        self.key
+    }
+
+    /// Adds the chroma of a frame and returns the key of the window.
+    pub fn push(&mut self, chroma: &[f32; 12]) -> Option<Key> {
+        if self.history.len() == self.window {
+            self.history.pop_front();
+        }
+        self.history.push_back(*chroma);
+        let mut sum = [0.0; 12];
+        for frame in self.history.iter() {
+            sum.iter_mut().zip(frame.iter()).for_each(|(s, v)| *s += v);
+        }
+        self.key = detect_key(&sum);
+        self.key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Chroma with the given pitch classes at 1.
+    fn notes(classes: &[usize]) -> [f32; 12] {
+        let mut chroma = [0.0; 12];
+        classes.iter().for_each(|&class| chroma[class] = 1.0);
+        chroma
+    }
+
+    #[test]
+    fn profiles_detect_their_own_key() {
+        for tonic in 0..12 {
+            let rotated: [f32; 12] =
+                std::array::from_fn(|class| MINOR_PROFILE[(class + 12 - tonic) % 12]);
+            let key = detect_key(&rotated).unwrap();
+            assert_eq!((key.tonic, key.mode), (tonic, Mode::Minor));
+            assert!((key.correlation - 1.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn chord_progressions_over_a_window() {
+        // I IV V I in G major
+        let mut detector = KeyDetector::new(16);
+        for chord in [[7, 11, 2], [0, 4, 7], [2, 6, 9], [7, 11, 2]] {
+            for _ in 0..4 {
+                detector.push(&notes(&chord));
+            }
+        }
+        assert_eq!(detector.key().unwrap().to_string(), "G major");
+
+        // i iv V i in A minor, the G major frames leave the window
+        for chord in [[9, 0, 4], [2, 5, 9], [4, 8, 11], [9, 0, 4]] {
+            for _ in 0..4 {
+                detector.push(&notes(&chord));
+            }
+        }
+        assert_eq!(detector.key().unwrap().to_string(), "A minor");
+    }
+
+    #[test]
+    fn silence_has_no_key() {
+        assert_eq!(detect_key(&[0.0; 12]), None);
+        assert_eq!(KeyDetector::new(4).push(&[0.5; 12]), None);
+    }
+}
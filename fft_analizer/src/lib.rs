@@ -1,26 +1,311 @@
+mod channel;
+pub mod chord;
+pub mod chroma;
+pub mod cqt;
+pub mod descriptors;
+pub mod filterbank;
+mod framer;
+mod frequency_axis;
+#[deprecated(note = "use the `window` module")]
 pub mod hann_window;
+pub mod key;
+pub mod log_bins;
+pub mod mfcc;
+mod normalization;
+pub mod peaks;
+pub mod phase;
+mod scale;
+pub mod stft;
+#[cfg(test)]
+mod test_utils;
+mod transform;
+pub mod window;
+
+pub use channel::{ChannelMode, ChannelSpectra};
+pub use framer::Framer;
+pub use frequency_axis::FrequencyAxis;
+pub use normalization::{Normalization, NormalizationError, Normalizer};
+pub use rustfft::num_complex::Complex;
+pub use scale::Scale;
+pub use transform::FftMode;
+use transform::Transform;
+use window::{Window, WindowFunction};
 
-use hann_window::HannWindow;
-use rustfft::{num_complex::Complex, FftPlanner};
+/// Sample rate assumed until [`FrequencySpectrum::with_sample_rate`] is called.
+pub const DEFAULT_SAMPLE_RATE: f32 = 44_100.0;
 
 /// A struct for computing the frequency spectrum of audio samples using FFT.
+///
+/// The FFT plan and every working buffer are created once in [`FrequencySpectrum::new`],
+/// so computing a spectrum does not allocate.
 pub struct FrequencySpectrum {
-    hann_window: HannWindow,
+    window: Window,
     samples_mut: Vec<f32>,
     channels: u16,
+    channel_mode: ChannelMode,
+    sample_rate: f32,
+    /// Spectra computed per second, `None` for one per window
+    frame_rate: Option<f32>,
+    transform: Transform,
+    scale: Scale,
+    /// One normalizer per output channel
+    normalizers: Vec<Normalizer>,
+    /// Whether the normalization was chosen by the caller rather than following the scale
+    explicit_normalization: bool,
+    /// Output of the last computed spectra
+    spectra: ChannelSpectra,
+    /// Complex bins of the last computed spectra, laid out like `spectra`
+    complex: Vec<Complex<f32>>,
 }
 
 impl FrequencySpectrum {
+    /// Creates an analyzer for frames of `samples_len` interleaved samples, using a Hann window.
+    ///
+    /// The sample rate defaults to [`DEFAULT_SAMPLE_RATE`] and channels are downmixed to mono.
+    /// Magnitudes are linear and normalized per frame between 0 and 1.
+    ///
+    /// # Panics
+    ///
+    /// If `channels` is 0 or `samples_len` holds less than one sample per channel.
     pub fn new(samples_len: usize, channels: u16) -> Self {
+        if channels == 0 {
+            panic!("channels ({}) must be at least 1", channels)
+        }
         let len = samples_len / channels as usize;
-        let hann_window = HannWindow::new(len);
+        if len == 0 {
+            panic!(
+                "samples len ({}) must hold at least one sample for each of the {} channels",
+                samples_len, channels
+            )
+        }
+        let window = Window::new(WindowFunction::Hann, len);
         let samples_mut = vec![0.0; len];
+        let transform = Transform::new(FftMode::default(), len);
+        let spectra = ChannelSpectra::new(1, len / 2);
         FrequencySpectrum {
-            hann_window,
+            window,
             samples_mut,
             channels,
+            channel_mode: ChannelMode::default(),
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            frame_rate: None,
+            transform,
+            scale: Scale::default(),
+            normalizers: vec![Normalizer::new(Normalization::default())],
+            explicit_normalization: false,
+            spectra,
+            complex: vec![Complex::new(0.0, 0.0); len / 2],
+        }
+    }
+
+    /// Sets the sample rate of the input, used to map bins to frequencies.
+    ///
+    /// # Panics
+    ///
+    /// If `sample_rate` is not a finite positive number.
+    pub fn with_sample_rate(mut self, sample_rate: f32) -> Self {
+        if !(sample_rate.is_finite() && sample_rate > 0.0) {
+            panic!("sample rate ({}) must be finite and positive", sample_rate)
+        }
+        self.sample_rate = sample_rate;
+        self
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    /// Sets how many spectra are computed per second, used to convert the time constants
+    /// of [`Normalization::Agc`] into frames. Defaults to one spectrum per window, i.e.
+    /// frames that don't overlap.
+    ///
+    /// # Panics
+    ///
+    /// If `frame_rate` is not a finite positive number.
+    pub fn with_frame_rate(mut self, frame_rate: f32) -> Self {
+        if !(frame_rate.is_finite() && frame_rate > 0.0) {
+            panic!("frame rate ({}) must be finite and positive", frame_rate)
+        }
+        self.frame_rate = Some(frame_rate);
+        self
+    }
+
+    pub fn frame_rate(&self) -> f32 {
+        self.frame_rate
+            .unwrap_or(self.sample_rate / self.window.len().max(1) as f32)
+    }
+
+    /// Selects which spectra are computed from the interleaved channels.
+    ///
+    /// # Panics
+    ///
+    /// If [`ChannelMode::Channel`] is out of range or [`ChannelMode::MidSide`] is used
+    /// without exactly two channels.
+    pub fn with_channel_mode(mut self, channel_mode: ChannelMode) -> Self {
+        match channel_mode {
+            ChannelMode::Channel(channel) if channel >= self.channels => panic!(
+                "channel ({}) is out of range for {} channels",
+                channel, self.channels
+            ),
+            ChannelMode::MidSide if self.channels != 2 => {
+                panic!("mid/side needs 2 channels, input has {}", self.channels)
+            }
+            _ => {}
         }
+        self.channel_mode = channel_mode;
+        let outputs = channel_mode.outputs(self.channels);
+        self.normalizers = (0..outputs)
+            .map(|_| Normalizer::new(self.normalization()))
+            .collect();
+        self.resize_outputs(outputs, self.spectrum_len());
+        self
+    }
+
+    pub fn channel_mode(&self) -> ChannelMode {
+        self.channel_mode
+    }
+
+    /// Replaces the window applied before the FFT.
+    pub fn with_window(mut self, function: WindowFunction) -> Self {
+        self.window = Window::new(function, self.window.len());
+        self
+    }
+
+    /// Zero-pads each windowed frame up to `fft_size` samples before the FFT.
+    ///
+    /// Interpolates the spectrum with more, narrower bins without collecting more audio.
+    ///
+    /// # Panics
+    ///
+    /// If `fft_size` is lower than the window length.
+    pub fn with_fft_size(mut self, fft_size: usize) -> Self {
+        if fft_size < self.window.len() {
+            panic!(
+                "fft size ({}) is lower than window len ({})",
+                fft_size,
+                self.window.len()
+            )
+        }
+        self.samples_mut = vec![0.0; fft_size];
+        self.transform = Transform::new(self.transform.mode(), fft_size);
+        self.resize_outputs(self.spectra.num_channels(), fft_size / 2);
+        self
+    }
+
+    /// Selects the FFT algorithm, both produce the same bins.
+    pub fn with_fft_mode(mut self, mode: FftMode) -> Self {
+        self.transform = Transform::new(mode, self.samples_mut.len());
+        self
+    }
+
+    pub fn fft_mode(&self) -> FftMode {
+        self.transform.mode()
+    }
+
+    /// Selects the scale of the returned magnitudes.
+    ///
+    /// Unless [`FrequencySpectrum::with_normalization`] was called, [`Scale::Dbfs`] switches
+    /// normalization to [`Normalization::None`] so levels stay relative to full scale.
+    ///
+    /// # Panics
+    ///
+    /// If the normalization set with [`FrequencySpectrum::with_normalization`] can't be used
+    /// with `scale`, see [`Normalization::validate`].
+    pub fn with_scale(mut self, scale: Scale) -> Self {
+        if self.explicit_normalization {
+            if let Err(error) = self.normalization().validate(scale) {
+                panic!("{}", error)
+            }
+        } else {
+            let normalization = match scale {
+                Scale::Dbfs { .. } => Normalization::None,
+                Scale::Linear | Scale::Power => Normalization::default(),
+            };
+            self.set_normalization(normalization);
+        }
+        self.scale = scale;
+        self
+    }
+
+    pub fn scale(&self) -> Scale {
+        self.scale
+    }
+
+    /// Selects how each frame is normalized, [`Normalization::None`] keeps absolute levels.
+    ///
+    /// # Panics
+    ///
+    /// If a parameter is out of range or the strategy can't be used with the configured
+    /// scale, see [`Normalization::validate`].
+    pub fn with_normalization(mut self, normalization: Normalization) -> Self {
+        if let Err(error) = normalization.validate(self.scale) {
+            panic!("{}", error)
+        }
+        self.explicit_normalization = true;
+        self.set_normalization(normalization);
+        self
+    }
+
+    fn set_normalization(&mut self, normalization: Normalization) {
+        self.normalizers
+            .iter_mut()
+            .for_each(|normalizer| *normalizer = Normalizer::new(normalization));
+    }
+
+    pub fn normalization(&self) -> Normalization {
+        self.normalizers[0].normalization()
+    }
+
+    /// Window applied before the FFT, use it to correct amplitudes by its coherent gain.
+    pub fn window(&self) -> &Window {
+        &self.window
     }
+
+    /// Length of the FFT including zero padding, use it to map spectrum indices to frequencies.
+    pub fn fft_size(&self) -> usize {
+        self.samples_mut.len()
+    }
+
+    /// Number of samples per channel in each analysed frame.
+    pub fn window_len(&self) -> usize {
+        self.window.len()
+    }
+
+    /// Number of frequency bins produced by [`FrequencySpectrum::frequency_spectrum`].
+    pub fn spectrum_len(&self) -> usize {
+        self.spectra.spectrum_len()
+    }
+
+    /// Mapping between spectrum indices and frequencies for the current configuration.
+    pub fn axis(&self) -> FrequencyAxis {
+        FrequencyAxis::new(self.sample_rate, self.fft_size())
+    }
+
+    /// Distance between two bins in Hz.
+    pub fn bin_width(&self) -> f32 {
+        self.axis().bin_width()
+    }
+
+    /// Centre frequency in Hz of spectrum index `index`, i.e. FFT bin `index + 1`.
+    pub fn bin_frequency(&self, index: usize) -> f32 {
+        self.axis().bin_frequency(index)
+    }
+
+    /// Spectrum index nearest to `hz`, `None` if it is the DC bin or above Nyquist.
+    pub fn frequency_to_bin(&self, hz: f32) -> Option<usize> {
+        self.axis().frequency_to_bin(hz)
+    }
+
+    /// `(hz, magnitude)` pairs of the first channel of the last computed spectra.
+    pub fn bins(&self) -> impl Iterator<Item = (f32, f32)> + '_ {
+        let axis = self.axis();
+        self.spectra
+            .channel(0)
+            .iter()
+            .enumerate()
+            .map(move |(i, &magnitude)| (axis.bin_frequency(i), magnitude))
+    }
+
     /// Computes the frequency spectrum of audio samples using FFT.
     ///
     /// Takes a slice of audio samples and computes the FFT (Fast Fourier Transform)
@@ -29,9 +314,11 @@ impl FrequencySpectrum {
     /// If `channels` is 1, assumes mono audio and directly uses `samples`.
     /// If `channels` is greater than 1, assumes interleaved stereo or multi-channel audio
     /// and averages samples across channels before computing FFT.
+    /// With another [`ChannelMode`] this is the first spectrum of [`FrequencySpectrum::channel_spectra`].
     ///
-    /// Applies a Hann window to the samples before FFT to reduce spectral leakage.
-    /// Normalizes the FFT output to ensure consistent magnitude scaling.
+    /// Applies the configured window (Hann by default) to the samples before FFT to reduce spectral leakage,
+    /// then zero-pads them up to the configured FFT size.
+    /// Converts the FFT output to the configured [`Scale`] and applies the configured [`Normalization`].
     ///
     /// # Arguments
     ///
@@ -40,16 +327,110 @@ impl FrequencySpectrum {
     ///
     /// # Returns
     ///
-    /// A vector containing the magnitudes of the frequency bins from the FFT,
-    /// ignoring the DC component
+    /// A slice containing the magnitudes of the frequency bins from the FFT,
+    /// ignoring the DC component. It is overwritten by the next call.
     ///
-    pub fn frequency_spectrum(&mut self, samples: &[f32]) -> Vec<f32> {
-        self.mix_channels(samples);
-        self.hann_window.apply(&mut self.samples_mut);
-        let mut spectrum = self.fft();
-        // spectrum = FrequencySpectrum::logarithmic_bins(&spectrum, spectrum.len());
-        FrequencySpectrum::normalize(&mut spectrum);
-        spectrum
+    pub fn frequency_spectrum(&mut self, samples: &[f32]) -> &[f32] {
+        self.channel_spectra(samples).channel(0)
+    }
+
+    /// Computes one spectrum per output channel of the configured [`ChannelMode`].
+    ///
+    /// Each spectrum is computed like [`FrequencySpectrum::frequency_spectrum`]
+    /// and normalized independently.
+    pub fn channel_spectra(&mut self, samples: &[f32]) -> &ChannelSpectra {
+        let frame_rate = self.frame_rate();
+        for output in 0..self.normalizers.len() {
+            self.fill_frame(samples, output);
+            let (frame, padding) = self.samples_mut.split_at_mut(self.window.len());
+            self.window.apply(frame);
+            padding.fill(0.0);
+            self.fft(output);
+            self.normalizers[output].apply(self.spectra.channel_mut(output), frame_rate);
+        }
+        &self.spectra
+    }
+
+    /// Computes the complex FFT bins of the first output channel.
+    ///
+    /// Bins are laid out like [`FrequencySpectrum::frequency_spectrum`]: the DC bin is skipped,
+    /// so index `i` is FFT bin `i + 1`, and the last index is the Nyquist bin when the FFT size
+    /// is even. Values are the raw FFT output of the windowed, zero-padded frame, without
+    /// [`Scale`] or [`Normalization`].
+    pub fn complex_spectrum(&mut self, samples: &[f32]) -> &[Complex<f32>] {
+        self.channel_spectra(samples);
+        self.complex_bins(0)
+    }
+
+    /// Complex bins of output channel `output` from the last computed spectra.
+    pub fn complex_bins(&self, output: usize) -> &[Complex<f32>] {
+        let len = self.spectrum_len();
+        &self.complex[output * len..(output + 1) * len]
+    }
+
+    /// Computes the linear magnitude and the phase, unwrapped along frequency,
+    /// of the first output channel. Bins are laid out like [`FrequencySpectrum::complex_spectrum`].
+    ///
+    /// # Panics
+    ///
+    /// If `magnitude` or `phase` length is different than [`FrequencySpectrum::spectrum_len`].
+    pub fn magnitude_phase(&mut self, samples: &[f32], magnitude: &mut [f32], phase: &mut [f32]) {
+        if magnitude.len() != self.spectrum_len() || phase.len() != self.spectrum_len() {
+            panic!(
+                "magnitude len ({}) and phase len ({}) must be {}",
+                magnitude.len(),
+                phase.len(),
+                self.spectrum_len()
+            )
+        }
+        let bins = self.complex_spectrum(samples);
+        for ((m, p), bin) in magnitude.iter_mut().zip(phase.iter_mut()).zip(bins.iter()) {
+            *m = bin.norm();
+            *p = bin.arg();
+        }
+        phase::unwrap(phase);
+    }
+
+    /// Same as [`FrequencySpectrum::frequency_spectrum`] but writes the magnitudes into `out`.
+    ///
+    /// Does not allocate, so it can be called from a real-time thread.
+    ///
+    /// # Panics
+    ///
+    /// If `out` length is different than [`FrequencySpectrum::spectrum_len`].
+    pub fn frequency_spectrum_into(&mut self, samples: &[f32], out: &mut [f32]) {
+        if out.len() != self.spectrum_len() {
+            panic!(
+                "out len ({}) is different than spectrum len ({})",
+                out.len(),
+                self.spectrum_len()
+            )
+        }
+        out.copy_from_slice(self.frequency_spectrum(samples));
+    }
+
+    fn resize_outputs(&mut self, outputs: usize, spectrum_len: usize) {
+        self.spectra = ChannelSpectra::new(outputs, spectrum_len);
+        self.complex = vec![Complex::new(0.0, 0.0); outputs * spectrum_len];
+    }
+
+    /// Writes the samples of output channel `output` into the frame.
+    fn fill_frame(&mut self, samples: &[f32], output: usize) {
+        let channels = self.channels as usize;
+        let frames = samples
+            .chunks_exact(channels)
+            .zip(self.samples_mut.iter_mut());
+        match self.channel_mode {
+            ChannelMode::Mono => self.mix_channels(samples),
+            ChannelMode::PerChannel => frames.for_each(|(frame, s)| *s = frame[output]),
+            ChannelMode::Channel(channel) => {
+                frames.for_each(|(frame, s)| *s = frame[channel as usize])
+            }
+            ChannelMode::MidSide => {
+                let sign = if output == 0 { 1.0 } else { -1.0 };
+                frames.for_each(|(frame, s)| *s = (frame[0] + sign * frame[1]) / 2.0)
+            }
+        }
     }
 
     /// Mixes audio samples across channels by averaging them.
@@ -67,8 +448,8 @@ impl FrequencySpectrum {
         } else {
             // Multi-channel audio: average samples across channels
             let mut j = 0;
-            for i in 0..samples.len() {
-                v += samples[i];
+            for (i, sample) in samples.iter().enumerate() {
+                v += sample;
                 if (i + 1) % self.channels as usize == 0 {
                     self.samples_mut[j] = v / self.channels as f32;
                     j += 1;
@@ -81,26 +462,20 @@ impl FrequencySpectrum {
     /// Computes fft over given samples.
     ///
     /// The second half and the DC are discarted since they are not relevant for audio processing.
-    fn fft(&mut self) -> Vec<f32> {
-        let mut planner = FftPlanner::<f32>::new();
-        let fft = planner.plan_fft_forward(self.samples_mut.len());
-        let mut spectrum: Vec<Complex<f32>> = self
-            .samples_mut
-            .iter()
-            .map(|&sample| Complex::new(sample, 0.0))
-            .collect();
-        fft.process(&mut spectrum);
-        let half = spectrum.len() / 2;
-        spectrum
-            .iter()
-            .skip(1)
-            .take(half)
-            .map(|sample| sample.norm())
-            .collect()
+    fn fft(&mut self, output: usize) {
+        let full_scale = self.window.len() as f32 * self.window.coherent_gain() / 2.0;
+        let bins = self.transform.process(&mut self.samples_mut);
+        let len = self.spectra.spectrum_len();
+        let complex = &mut self.complex[output * len..(output + 1) * len];
+        complex.copy_from_slice(&bins[1..=len]);
+        let spectrum = self.spectra.channel_mut(output);
+        for (magnitude, value) in spectrum.iter_mut().zip(complex.iter()) {
+            *magnitude = self.scale.apply(*value, full_scale);
+        }
     }
 
     /// Normalizes between 0 and 1
-    fn normalize(input: &mut Vec<f32>) {
+    fn normalize(input: &mut [f32]) {
         if input.is_empty() {
             return;
         }
@@ -109,14 +484,14 @@ impl FrequencySpectrum {
             .iter()
             .min_by(|a, b| {
                 a.partial_cmp(b)
-                    .expect(&format!("Can't compare this values: {} {}", a, b))
+                    .unwrap_or_else(|| panic!("Can't compare this values: {} {}", a, b))
             })
             .expect("This vector shouln'd be empty");
         let max = *input
             .iter()
             .max_by(|a, b| {
                 a.partial_cmp(b)
-                    .expect(&format!("Can't compare this values: {} {}", a, b))
+                    .unwrap_or_else(|| panic!("Can't compare this values: {} {}", a, b))
             })
             .expect("This vector shouln'd be empty");
 
@@ -137,23 +512,6 @@ impl FrequencySpectrum {
             }
         }
     }
-
-    fn _logarithmic_bins(spectrum: &[f32], num_bins: usize) -> Vec<f32> {
-        let n = spectrum.len();
-        let mut bins = Vec::with_capacity(num_bins);
-
-        for i in 0..num_bins {
-            // map i ∈ [0,num_bins-1] to log-space index
-            let norm_i = i as f32 / (num_bins - 1) as f32; // 0..1
-                                                           // exponent > 1 to concentrate bins at low frequencies
-            let exp_factor = 2.5; // tweak: higher -> more low-freq resolution
-            let log_idx = ((norm_i).powf(exp_factor)) * (n - 1) as f32;
-            let idx = log_idx.min((n - 1) as f32) as usize;
-            bins.push(spectrum[idx]);
-        }
-
-        bins
-    }
 }
 
 #[cfg(test)]
@@ -161,24 +519,496 @@ mod tests {
     use std::f32::consts::PI;
 
     use super::*;
+    use crate::test_utils::{noise, sine};
 
     fn sinus_wave() -> Vec<f32> {
         let mut res: Vec<f32> = vec![0.0; 360];
-        for e in 0..res.len() {
+        for (e, value) in res.iter_mut().enumerate() {
             let sin = (e as f32 * PI / 180.0).sin() + 1.0;
-            res[e] = sin;
+            *value = sin;
         }
         res
     }
 
     #[test]
     fn should_fill_first_bin_to_one_for_sinus_waves() {
-        let mut samples = sinus_wave();
+        let samples = sinus_wave();
         let mut fs = FrequencySpectrum::new(samples.len(), 1);
-        let res = fs.frequency_spectrum(&mut samples);
+        let res = fs.frequency_spectrum(&samples);
         assert_eq!(res[0], 1.0);
     }
 
+    #[test]
+    fn real_and_complex_fft_modes_agree() {
+        for samples in [sinus_wave(), noise(1024, 12345), noise(1023, 12345)] {
+            let mut real = FrequencySpectrum::new(samples.len(), 1).with_fft_mode(FftMode::Real);
+            let mut complex =
+                FrequencySpectrum::new(samples.len(), 1).with_fft_mode(FftMode::Complex);
+            let a = real.frequency_spectrum(&samples);
+            let b = complex.frequency_spectrum(&samples);
+            assert_eq!(a.len(), b.len());
+            for (x, y) in a.iter().zip(b.iter()) {
+                assert!((x - y).abs() < 1e-4, "{} != {}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn dbfs_full_scale_sine_reads_zero_db() {
+        let samples = sine(64.0, 1024.0, 1024);
+        let mut fs = FrequencySpectrum::new(samples.len(), 1)
+            .with_scale(Scale::Dbfs { floor: -120.0 })
+            .with_normalization(Normalization::None);
+        let res = fs.frequency_spectrum(&samples);
+        // DC is skipped, bin 64 is at index 63
+        assert!(res[63].abs() < 0.1, "{}", res[63]);
+        assert!(res.iter().all(|&db| db <= 0.1));
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_an_empty_frame() {
+        let _ = FrequencySpectrum::new(1, 2);
+    }
+
+    #[test]
+    fn dbfs_defaults_to_no_normalization() {
+        let samples = sine(64.0, 1024.0, 1024);
+        let mut fs =
+            FrequencySpectrum::new(samples.len(), 1).with_scale(Scale::Dbfs { floor: -120.0 });
+        assert_eq!(fs.normalization(), Normalization::None);
+        let res = fs.frequency_spectrum(&samples);
+        assert!(res[63].abs() < 0.1, "{}", res[63]);
+        let fs = FrequencySpectrum::new(samples.len(), 1)
+            .with_normalization(Normalization::MinMax)
+            .with_scale(Scale::Dbfs { floor: -120.0 });
+        assert_eq!(fs.normalization(), Normalization::MinMax);
+    }
+
+    #[test]
+    fn dbfs_flat_top_reads_zero_db_between_bins() {
+        let samples = sine(64.5, 1024.0, 1024);
+        let mut fs = FrequencySpectrum::new(samples.len(), 1)
+            .with_window(WindowFunction::FlatTop)
+            .with_scale(Scale::Dbfs { floor: -120.0 })
+            .with_normalization(Normalization::None);
+        let res = fs.frequency_spectrum(&samples);
+        let max = res.iter().cloned().fold(f32::MIN, f32::max);
+        assert!(max.abs() < 0.1, "{}", max);
+    }
+
+    #[test]
+    fn dbfs_silence_reads_floor() {
+        let samples = vec![0.0; 1024];
+        let mut fs = FrequencySpectrum::new(samples.len(), 1)
+            .with_scale(Scale::Dbfs { floor: -120.0 })
+            .with_normalization(Normalization::None);
+        let res = fs.frequency_spectrum(&samples);
+        assert!(res.iter().all(|&db| db == -120.0));
+    }
+
+    #[test]
+    fn power_is_squared_linear_magnitude() {
+        let samples = noise(512, 12345);
+        let mut linear =
+            FrequencySpectrum::new(samples.len(), 1).with_normalization(Normalization::None);
+        let mut power = FrequencySpectrum::new(samples.len(), 1)
+            .with_scale(Scale::Power)
+            .with_normalization(Normalization::None);
+        let a = linear.frequency_spectrum(&samples);
+        let b = power.frequency_spectrum(&samples);
+        for (m, p) in a.iter().zip(b.iter()) {
+            assert!((m * m - p).abs() < 1e-3 * p.max(1.0));
+        }
+    }
+
+    /// Peak of the sinus wave spectrum before any normalization.
+    fn sinus_wave_peak() -> f32 {
+        let samples = sinus_wave();
+        let mut fs =
+            FrequencySpectrum::new(samples.len(), 1).with_normalization(Normalization::None);
+        fs.frequency_spectrum(&samples)[0]
+    }
+
+    fn scaled(samples: &[f32], gain: f32) -> Vec<f32> {
+        samples.iter().map(|s| s * gain).collect()
+    }
+
+    #[test]
+    fn fixed_normalization_divides_by_reference() {
+        let samples = sinus_wave();
+        let peak = sinus_wave_peak();
+        let mut fs = FrequencySpectrum::new(samples.len(), 1)
+            .with_normalization(Normalization::Fixed { reference: peak });
+        let res = fs.frequency_spectrum(&samples);
+        assert!((res[0] - 1.0).abs() < 1e-5);
+
+        let res = fs.frequency_spectrum(&scaled(&samples, 0.5));
+        assert!((res[0] - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn running_peak_keeps_quiet_frames_quiet() {
+        let samples = sinus_wave();
+        let mut fs = FrequencySpectrum::new(samples.len(), 1)
+            .with_normalization(Normalization::RunningPeak { decay: 0.9 });
+        let res = fs.frequency_spectrum(&samples);
+        assert!((res[0] - 1.0).abs() < 1e-5);
+
+        // The peak decays to 0.9 so a half amplitude frame reads 0.5 / 0.9
+        let res = fs.frequency_spectrum(&scaled(&samples, 0.5));
+        assert!((res[0] - 0.5 / 0.9).abs() < 1e-4);
+
+        // Once the peak has decayed below the frame max it follows the frame
+        for _ in 0..20 {
+            fs.frequency_spectrum(&scaled(&samples, 0.5));
+        }
+        let res = fs.frequency_spectrum(&scaled(&samples, 0.5));
+        assert!((res[0] - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn running_peak_silence_stays_at_zero() {
+        let samples = vec![0.0; 360];
+        let mut fs = FrequencySpectrum::new(samples.len(), 1)
+            .with_normalization(Normalization::RunningPeak { decay: 0.9 });
+        let res = fs.frequency_spectrum(&samples);
+        assert!(res.iter().all(|&v| v == 0.0));
+    }
+
+    #[test]
+    fn agc_follows_long_term_maximum() {
+        let samples = sinus_wave();
+        // One frame of attack and 50 of release
+        let mut fs = FrequencySpectrum::new(samples.len(), 1)
+            .with_frame_rate(100.0)
+            .with_normalization(Normalization::Agc {
+                attack: 0.01,
+                release: 0.5,
+            });
+        for _ in 0..30 {
+            fs.frequency_spectrum(&samples);
+        }
+        let res = fs.frequency_spectrum(&samples);
+        assert!((res[0] - 1.0).abs() < 1e-3);
+
+        // A quieter frame is not stretched to full scale because of the slow release
+        let res = fs.frequency_spectrum(&scaled(&samples, 0.25));
+        assert!(res[0] < 0.3, "{}", res[0]);
+
+        // A louder frame goes above 1 until the fast attack catches up
+        let res = fs.frequency_spectrum(&scaled(&samples, 2.0));
+        assert!(res[0] > 1.0);
+        for _ in 0..30 {
+            fs.frequency_spectrum(&scaled(&samples, 2.0));
+        }
+        let res = fs.frequency_spectrum(&scaled(&samples, 2.0));
+        assert!((res[0] - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn agc_time_constants_are_in_seconds() {
+        let samples = sinus_wave();
+        let agc = Normalization::Agc {
+            attack: 0.0,
+            release: 1.0,
+        };
+        let mut slow = FrequencySpectrum::new(samples.len(), 1)
+            .with_frame_rate(10.0)
+            .with_normalization(agc);
+        let mut fast = FrequencySpectrum::new(samples.len(), 1)
+            .with_frame_rate(1000.0)
+            .with_normalization(agc);
+        slow.frequency_spectrum(&samples);
+        fast.frequency_spectrum(&samples);
+        // Release over one frame, a tenth and a thousandth of the time constant
+        let slow = slow.frequency_spectrum(&scaled(&samples, 0.5))[0];
+        let fast = fast.frequency_spectrum(&scaled(&samples, 0.5))[0];
+        let level = |frames: f32| 1.0 - 0.5 * (1.0 - (-1.0 / frames).exp());
+        assert!((slow - 0.5 / level(10.0)).abs() < 1e-4, "{}", slow);
+        assert!((fast - 0.5 / level(1000.0)).abs() < 1e-4, "{}", fast);
+    }
+
+    #[test]
+    fn invalid_normalizations_are_rejected() {
+        for normalization in [
+            Normalization::Fixed { reference: 0.0 },
+            Normalization::Fixed {
+                reference: f32::INFINITY,
+            },
+            Normalization::RunningPeak { decay: 1.5 },
+            Normalization::RunningPeak { decay: -0.1 },
+            Normalization::Agc {
+                attack: f32::NAN,
+                release: 1.0,
+            },
+            Normalization::Agc {
+                attack: 0.1,
+                release: -1.0,
+            },
+        ] {
+            assert!(matches!(
+                normalization.validate(Scale::Linear),
+                Err(NormalizationError::Parameter(_))
+            ));
+        }
+        let dbfs = Scale::Dbfs { floor: -120.0 };
+        let peak = Normalization::RunningPeak { decay: 0.9 };
+        assert_eq!(
+            peak.validate(dbfs),
+            Err(NormalizationError::Scale(peak, dbfs))
+        );
+        assert_eq!(Normalization::MinMax.validate(dbfs), Ok(()));
+    }
+
+    #[test]
+    #[should_panic]
+    fn dividing_normalizations_reject_decibels() {
+        let _ = FrequencySpectrum::new(360, 1)
+            .with_normalization(Normalization::Fixed { reference: 1.0 })
+            .with_scale(Scale::Dbfs { floor: -120.0 });
+    }
+
+    #[test]
+    fn no_normalization_keeps_linear_magnitudes() {
+        let samples = sinus_wave();
+        let mut fs =
+            FrequencySpectrum::new(samples.len(), 1).with_normalization(Normalization::None);
+        let quiet = fs.frequency_spectrum(&scaled(&samples, 0.5))[0];
+        assert!((quiet - sinus_wave_peak() * 0.5).abs() < 1e-3);
+    }
+
+    #[test]
+    fn zero_padding_interpolates_the_spectrum() {
+        let samples = sine(16.0, 256.0, 256);
+        let mut fs = FrequencySpectrum::new(samples.len(), 1)
+            .with_fft_size(1024)
+            .with_scale(Scale::Dbfs { floor: -120.0 })
+            .with_normalization(Normalization::None);
+        assert_eq!(fs.window_len(), 256);
+        assert_eq!(fs.fft_size(), 1024);
+        assert_eq!(fs.spectrum_len(), 512);
+        let res = fs.frequency_spectrum(&samples);
+        // Bin 16 of the window is bin 64 of the padded FFT, at index 63
+        let peak = res
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
+            .unwrap();
+        assert_eq!(peak.0, 63);
+        assert!(peak.1.abs() < 0.1, "{}", peak.1);
+        // Padded bins between the original ones are interpolated, not empty
+        assert!(res[61] > -20.0);
+    }
+
+    #[test]
+    fn zero_padding_is_cleared_every_frame() {
+        let samples = noise(256, 12345);
+        for mode in [FftMode::Real, FftMode::Complex] {
+            let mut fs = FrequencySpectrum::new(samples.len(), 1)
+                .with_fft_mode(mode)
+                .with_fft_size(512);
+            let first = fs.frequency_spectrum(&samples).to_vec();
+            let second = fs.frequency_spectrum(&samples);
+            assert_eq!(first, second);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn fft_size_cannot_be_lower_than_window() {
+        let _ = FrequencySpectrum::new(256, 1).with_fft_size(128);
+    }
+
+    #[test]
+    fn sine_peak_is_at_its_frequency_after_the_dc_offset() {
+        let sample_rate = 8000.0;
+        // 1000 Hz is bin 128 of a 1024 FFT, i.e. index 127 once DC is skipped
+        let samples = sine(1000.0, sample_rate, 1024);
+        let mut fs = FrequencySpectrum::new(samples.len(), 1).with_sample_rate(sample_rate);
+        fs.frequency_spectrum(&samples);
+        assert_eq!(fs.bin_width(), 7.8125);
+        assert_eq!(fs.frequency_to_bin(1000.0), Some(127));
+        assert_eq!(fs.bin_frequency(127), 1000.0);
+        let (hz, magnitude) = fs
+            .bins()
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+            .unwrap();
+        assert_eq!(hz, 1000.0);
+        assert_eq!(magnitude, 1.0);
+        assert_eq!(fs.bins().count(), fs.spectrum_len());
+    }
+
+    #[test]
+    fn bin_mapping_accounts_for_zero_padding() {
+        let fs = FrequencySpectrum::new(1024, 1)
+            .with_sample_rate(8000.0)
+            .with_fft_size(4096);
+        assert_eq!(fs.bin_width(), 8000.0 / 4096.0);
+        assert_eq!(fs.frequency_to_bin(1000.0), Some(511));
+    }
+
+    /// Interleaves two channels.
+    fn stereo(left: &[f32], right: &[f32]) -> Vec<f32> {
+        left.iter()
+            .zip(right.iter())
+            .flat_map(|(l, r)| [*l, *r])
+            .collect()
+    }
+
+    fn peak_index(spectrum: &[f32]) -> usize {
+        spectrum
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
+            .unwrap()
+            .0
+    }
+
+    #[test]
+    fn anti_phase_stereo_cancels_in_mono_but_not_per_channel() {
+        let left = sine(8.0, 256.0, 256);
+        let right: Vec<f32> = left.iter().map(|s| -s).collect();
+        let samples = stereo(&left, &right);
+
+        let mut mono =
+            FrequencySpectrum::new(samples.len(), 2).with_normalization(Normalization::None);
+        assert!(mono.frequency_spectrum(&samples).iter().all(|&m| m < 1e-3));
+
+        let mut per_channel = FrequencySpectrum::new(samples.len(), 2)
+            .with_channel_mode(ChannelMode::PerChannel)
+            .with_normalization(Normalization::None);
+        let spectra = per_channel.channel_spectra(&samples);
+        assert_eq!(spectra.num_channels(), 2);
+        assert_eq!(spectra.channel(0), spectra.channel(1));
+        assert_eq!(peak_index(spectra.channel(0)), 7);
+    }
+
+    #[test]
+    fn mid_side_separates_common_and_difference_signals() {
+        let common = sine(8.0, 256.0, 256);
+        let difference = sine(32.0, 256.0, 256);
+        let left: Vec<f32> = common.iter().zip(&difference).map(|(c, d)| c + d).collect();
+        let right: Vec<f32> = common.iter().zip(&difference).map(|(c, d)| c - d).collect();
+        let samples = stereo(&left, &right);
+
+        let mut fs = FrequencySpectrum::new(samples.len(), 2)
+            .with_channel_mode(ChannelMode::MidSide)
+            .with_normalization(Normalization::None);
+        let spectra = fs.channel_spectra(&samples);
+        assert_eq!(peak_index(spectra.channel(0)), 7);
+        assert_eq!(peak_index(spectra.channel(1)), 31);
+        assert!(spectra.channel(0)[31] < 1e-3);
+        assert!(spectra.channel(1)[7] < 1e-3);
+    }
+
+    #[test]
+    fn single_channel_selects_one_input_channel() {
+        let left = sine(8.0, 256.0, 256);
+        let right = sine(32.0, 256.0, 256);
+        let samples = stereo(&left, &right);
+        let mut fs =
+            FrequencySpectrum::new(samples.len(), 2).with_channel_mode(ChannelMode::Channel(1));
+        assert_eq!(fs.channel_spectra(&samples).num_channels(), 1);
+        assert_eq!(peak_index(fs.frequency_spectrum(&samples)), 31);
+    }
+
+    #[test]
+    #[should_panic]
+    fn sample_rate_must_be_positive() {
+        let _ = FrequencySpectrum::new(256, 1).with_sample_rate(0.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn mid_side_needs_stereo() {
+        let _ = FrequencySpectrum::new(300, 3).with_channel_mode(ChannelMode::MidSide);
+    }
+
+    #[test]
+    fn complex_spectrum_skips_dc_and_keeps_nyquist() {
+        // DC offset plus a full-scale Nyquist tone
+        let samples: Vec<f32> = (0..64)
+            .map(|n| 0.5 + if n % 2 == 0 { 1.0 } else { -1.0 })
+            .collect();
+        let mut fs = FrequencySpectrum::new(samples.len(), 1)
+            .with_window(WindowFunction::Tukey { alpha: 0.0 });
+        let bins = fs.complex_spectrum(&samples);
+        assert_eq!(bins.len(), 32);
+        // The DC energy is not in the first index
+        assert!(bins[0].norm() < 1e-3);
+        // The last index is the Nyquist bin, purely real
+        assert!((bins[31].re - 64.0).abs() < 1e-3);
+        assert!(bins[31].im.abs() < 1e-3);
+    }
+
+    #[test]
+    fn complex_spectrum_matches_linear_magnitudes() {
+        let samples = noise(512, 12345);
+        let mut fs =
+            FrequencySpectrum::new(samples.len(), 1).with_normalization(Normalization::None);
+        let magnitudes = fs.frequency_spectrum(&samples).to_vec();
+        let bins = fs.complex_spectrum(&samples);
+        for (m, bin) in magnitudes.iter().zip(bins.iter()) {
+            assert!((m - bin.norm()).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn phase_of_cosine_and_sine() {
+        let len = 256;
+        let cosine: Vec<f32> = (0..len)
+            .map(|n| (2.0 * PI * 8.0 * n as f32 / len as f32).cos())
+            .collect();
+        let mut fs =
+            FrequencySpectrum::new(len, 1).with_window(WindowFunction::Tukey { alpha: 0.0 });
+        let mut magnitude = vec![0.0; fs.spectrum_len()];
+        let mut phase = vec![0.0; fs.spectrum_len()];
+        fs.magnitude_phase(&cosine, &mut magnitude, &mut phase);
+        assert!((magnitude[7] - 128.0).abs() < 1e-2);
+        assert!(phase::principal_argument(phase[7]).abs() < 1e-3);
+
+        let sine = sine(8.0, len as f32, len);
+        fs.magnitude_phase(&sine, &mut magnitude, &mut phase);
+        assert!((phase::principal_argument(phase[7]) + PI / 2.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn delayed_impulse_has_linear_unwrapped_phase() {
+        let mut samples = vec![0.0; 128];
+        samples[5] = 1.0;
+        let mut fs = FrequencySpectrum::new(samples.len(), 1)
+            .with_window(WindowFunction::Tukey { alpha: 0.0 });
+        let mut magnitude = vec![0.0; fs.spectrum_len()];
+        let mut phase = vec![0.0; fs.spectrum_len()];
+        fs.magnitude_phase(&samples, &mut magnitude, &mut phase);
+        for (i, p) in phase.iter().enumerate().take(63) {
+            let expected = -2.0 * PI * 5.0 * (i + 1) as f32 / 128.0;
+            assert!((p - expected).abs() < 1e-3, "{} != {}", p, expected);
+        }
+    }
+
+    #[test]
+    fn frequency_spectrum_into_matches_frequency_spectrum() {
+        let samples = sinus_wave();
+        let mut fs = FrequencySpectrum::new(samples.len(), 1);
+        let expected = fs.frequency_spectrum(&samples).to_vec();
+        let mut out = vec![0.0; fs.spectrum_len()];
+        fs.frequency_spectrum_into(&samples, &mut out);
+        assert_eq!(expected, out);
+        fs.frequency_spectrum_into(&samples, &mut out);
+        assert_eq!(expected, out);
+    }
+
+    #[test]
+    #[should_panic]
+    fn frequency_spectrum_into_rejects_wrong_out_len() {
+        let samples = sinus_wave();
+        let mut fs = FrequencySpectrum::new(samples.len(), 1);
+        let mut out = vec![0.0; fs.spectrum_len() + 1];
+        fs.frequency_spectrum_into(&samples, &mut out);
+    }
+
     #[test]
     fn mix_channels() {
         let samples = vec![1.0, 2.0, 2.0, 3.0];
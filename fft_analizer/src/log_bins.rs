@@ -0,0 +1,232 @@
+/// How the FFT bins that fall inside a band are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Aggregation {
+    #[default]
+    Max,
+    Mean,
+    Rms,
+}
+
+/// A band of the logarithmic frequency axis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Band {
+    /// First spectrum index inside the band
+    start: usize,
+    /// One past the last spectrum index inside the band
+    end: usize,
+    /// Fractional spectrum index of the band centre, used when the band holds no bin
+    position: f32,
+}
+
+/// Rebins the output of [`crate::FrequencySpectrum::frequency_spectrum`] into
+/// logarithmically spaced frequency bands.
+///
+/// The band layout is computed once in [`LogBins::new`] and reused every frame.
+pub struct LogBins {
+    bands: Vec<Band>,
+    frequencies: Vec<f32>,
+    aggregation: Aggregation,
+}
+
+impl LogBins {
+    /// Creates `num_bands` bands between `f_min` and `f_max` Hz.
+    ///
+    /// `fft_size` is the FFT length that produced the spectrum, including zero padding
+    /// (see [`crate::FrequencySpectrum::fft_size`]). Spectrum index `i` is
+    /// the bin at `(i + 1) * sample_rate / fft_size` Hz since the DC is skipped.
+    ///
+    /// # Panics
+    ///
+    /// If `sample_rate` is not finite and positive, `fft_size` is 0, or `f_min` and `f_max`
+    /// are not finite with `f_min` positive and lower than `f_max`.
+    pub fn new(
+        sample_rate: f32,
+        fft_size: usize,
+        f_min: f32,
+        f_max: f32,
+        num_bands: usize,
+        aggregation: Aggregation,
+    ) -> Self {
+        if !(sample_rate.is_finite() && sample_rate > 0.0) {
+            panic!("sample rate ({}) must be finite and positive", sample_rate)
+        }
+        if fft_size == 0 {
+            panic!("fft size ({}) must be at least 1", fft_size)
+        }
+        if !(f_min.is_finite() && f_max.is_finite() && f_min > 0.0 && f_min < f_max) {
+            panic!(
+                "invalid frequency range: f_min ({}) must be positive and lower than f_max ({})",
+                f_min, f_max
+            )
+        }
+        let bin_width = sample_rate / fft_size as f32;
+        let ratio = f_max / f_min;
+        let edge = |b: usize| f_min * ratio.powf(b as f32 / num_bands as f32);
+        // Spectrum index of a frequency, the DC bin is not part of the spectrum
+        let index = |hz: f32| hz / bin_width - 1.0;
+
+        let mut bands = Vec::with_capacity(num_bands);
+        let mut frequencies = Vec::with_capacity(num_bands);
+        for b in 0..num_bands {
+            let (low, high) = (edge(b), edge(b + 1));
+            let center = (low * high).sqrt();
+            bands.push(Band {
+                start: index(low).ceil().max(0.0) as usize,
+                end: index(high).ceil().max(0.0) as usize,
+                position: index(center),
+            });
+            frequencies.push(center);
+        }
+
+        LogBins {
+            bands,
+            frequencies,
+            aggregation,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bands.is_empty()
+    }
+
+    /// Centre frequency of each band in Hz (geometric mean of its edges).
+    pub fn frequencies(&self) -> &[f32] {
+        &self.frequencies
+    }
+
+    /// Rebins `spectrum` into `out`.
+    ///
+    /// Bands that hold no FFT bin are linearly interpolated at their centre frequency,
+    /// bands above the last bin of `spectrum` (e.g. past Nyquist) are 0.
+    ///
+    /// # Panics
+    ///
+    /// If `out` length is different than [`LogBins::len`].
+    pub fn apply(&self, spectrum: &[f32], out: &mut [f32]) {
+        if out.len() != self.bands.len() {
+            panic!(
+                "out len ({}) is different than bands len ({})",
+                out.len(),
+                self.bands.len()
+            )
+        }
+        for (value, band) in out.iter_mut().zip(self.bands.iter()) {
+            let end = band.end.min(spectrum.len());
+            *value = if band.start >= spectrum.len() {
+                0.0
+            } else if band.start < end {
+                let bins = &spectrum[band.start..end];
+                match self.aggregation {
+                    Aggregation::Max => bins.iter().cloned().fold(f32::MIN, f32::max),
+                    Aggregation::Mean => bins.iter().sum::<f32>() / bins.len() as f32,
+                    Aggregation::Rms => {
+                        (bins.iter().map(|v| v * v).sum::<f32>() / bins.len() as f32).sqrt()
+                    }
+                }
+            } else {
+                interpolate(spectrum, band.position)
+            };
+        }
+    }
+}
+
+/// Linear interpolation of `spectrum` at a fractional index, clamped to its edges.
+fn interpolate(spectrum: &[f32], position: f32) -> f32 {
+    if spectrum.is_empty() {
+        return 0.0;
+    }
+    let last = spectrum.len() - 1;
+    let position = position.clamp(0.0, last as f32);
+    let i = position.floor() as usize;
+    let j = (i + 1).min(last);
+    let t = position - i as f32;
+    spectrum[i] * (1.0 - t) + spectrum[j] * t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 1024.0;
+    const FFT_SIZE: usize = 1024;
+
+    /// Spectrum whose value is the frequency of the bin, 1 Hz per bin.
+    fn ramp() -> Vec<f32> {
+        (1..=FFT_SIZE / 2).map(|hz| hz as f32).collect()
+    }
+
+    #[test]
+    fn band_centres_are_log_spaced() {
+        let bins = LogBins::new(SAMPLE_RATE, FFT_SIZE, 10.0, 160.0, 4, Aggregation::Max);
+        let expected = [
+            10.0 * 2f32.sqrt(),
+            20.0 * 2f32.sqrt(),
+            40.0 * 2f32.sqrt(),
+            80.0 * 2f32.sqrt(),
+        ];
+        for (f, e) in bins.frequencies().iter().zip(expected.iter()) {
+            assert!((f - e).abs() < 1e-3, "{} != {}", f, e);
+        }
+    }
+
+    #[test]
+    fn aggregates_bins_inside_each_band() {
+        let spectrum = ramp();
+        let mut out = [0.0; 1];
+        // Band [10, 20) Hz holds the bins at 10..=19 Hz
+        LogBins::new(SAMPLE_RATE, FFT_SIZE, 10.0, 20.0, 1, Aggregation::Max)
+            .apply(&spectrum, &mut out);
+        assert_eq!(out[0], 19.0);
+        LogBins::new(SAMPLE_RATE, FFT_SIZE, 10.0, 20.0, 1, Aggregation::Mean)
+            .apply(&spectrum, &mut out);
+        assert_eq!(out[0], 14.5);
+        LogBins::new(SAMPLE_RATE, FFT_SIZE, 10.0, 20.0, 1, Aggregation::Rms)
+            .apply(&spectrum, &mut out);
+        let rms = ((10..20).map(|v| (v * v) as f32).sum::<f32>() / 10.0).sqrt();
+        assert!((out[0] - rms).abs() < 1e-4);
+    }
+
+    #[test]
+    fn interpolates_bands_narrower_than_a_bin() {
+        let spectrum = ramp();
+        let bins = LogBins::new(SAMPLE_RATE, FFT_SIZE, 2.2, 2.8, 3, Aggregation::Max);
+        let mut out = [0.0; 3];
+        bins.apply(&spectrum, &mut out);
+        for (value, f) in out.iter().zip(bins.frequencies()) {
+            assert!((value - f).abs() < 1e-4, "{} != {}", value, f);
+        }
+    }
+
+    #[test]
+    fn bands_above_the_spectrum_are_zero() {
+        let spectrum = ramp();
+        let bins = LogBins::new(SAMPLE_RATE, FFT_SIZE, 100.0, 2000.0, 8, Aggregation::Mean);
+        let mut out = [0.0; 8];
+        bins.apply(&spectrum, &mut out);
+        // Band 4 spans 447 to 650 Hz, the spectrum stops at 512 Hz
+        assert_eq!(out[4], (448 + 512) as f32 / 2.0);
+        assert_eq!(out[5..], [0.0; 3]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_a_nan_frequency() {
+        LogBins::new(SAMPLE_RATE, FFT_SIZE, f32::NAN, 2000.0, 8, Aggregation::Max);
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_a_zero_fft_size() {
+        LogBins::new(SAMPLE_RATE, 0, 100.0, 2000.0, 8, Aggregation::Max);
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_a_zero_sample_rate() {
+        LogBins::new(0.0, FFT_SIZE, 100.0, 2000.0, 8, Aggregation::Max);
+    }
+}
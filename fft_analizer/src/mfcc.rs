@@ -0,0 +1,311 @@
+use std::{collections::VecDeque, f32::consts::PI};
+
+/// Energies below this value are clamped before taking the log.
+const LOG_FLOOR: f32 = 1e-10;
+
+/// Mel-frequency cepstral coefficients from the output of a mel [`crate::filterbank::Filterbank`].
+///
+/// Computes the log of the mel energies followed by an orthonormal DCT-II.
+/// The DCT matrix is computed once in [`Mfcc::new`].
+pub struct Mfcc {
+    num_mels: usize,
+    /// `num_coefficients` rows of `num_mels` DCT-II basis values
+    dct: Vec<f32>,
+    lifter: Vec<f32>,
+    log_mel: Vec<f32>,
+}
+
+impl Mfcc {
+    /// # Panics
+    ///
+    /// If `num_coefficients` is greater than `num_mels`.
+    pub fn new(num_mels: usize, num_coefficients: usize) -> Self {
+        if num_coefficients > num_mels {
+            panic!(
+                "num_coefficients ({}) is greater than num_mels ({})",
+                num_coefficients, num_mels
+            )
+        }
+        let mut dct = Vec::with_capacity(num_coefficients * num_mels);
+        for k in 0..num_coefficients {
+            let scale = if k == 0 {
+                (1.0 / num_mels as f32).sqrt()
+            } else {
+                (2.0 / num_mels as f32).sqrt()
+            };
+            for m in 0..num_mels {
+                dct.push(scale * (PI * k as f32 * (m as f32 + 0.5) / num_mels as f32).cos());
+            }
+        }
+        Mfcc {
+            num_mels,
+            dct,
+            lifter: vec![1.0; num_coefficients],
+            log_mel: vec![0.0; num_mels],
+        }
+    }
+
+    /// Applies HTK sinusoidal liftering `1 + L / 2 * sin(PI * n / L)`, 0 disables it.
+    pub fn with_lifter(mut self, lifter: f32) -> Self {
+        for (n, weight) in self.lifter.iter_mut().enumerate() {
+            *weight = if lifter > 0.0 {
+                1.0 + lifter / 2.0 * (PI * n as f32 / lifter).sin()
+            } else {
+                1.0
+            };
+        }
+        self
+    }
+
+    pub fn num_coefficients(&self) -> usize {
+        self.lifter.len()
+    }
+
+    /// Computes the coefficients of a frame of mel energies into `out`.
+    ///
+    /// # Panics
+    ///
+    /// If `mel` length is different than `num_mels` or `out` length is different
+    /// than [`Mfcc::num_coefficients`].
+    pub fn compute(&mut self, mel: &[f32], out: &mut [f32]) {
+        if mel.len() != self.num_mels || out.len() != self.lifter.len() {
+            panic!(
+                "mel len ({}) and out len ({}) must be {} and {}",
+                mel.len(),
+                out.len(),
+                self.num_mels,
+                self.lifter.len()
+            )
+        }
+        for (log, &energy) in self.log_mel.iter_mut().zip(mel.iter()) {
+            *log = energy.max(LOG_FLOOR).ln();
+        }
+        for (k, coefficient) in out.iter_mut().enumerate() {
+            let basis = &self.dct[k * self.num_mels..(k + 1) * self.num_mels];
+            let value: f32 = basis
+                .iter()
+                .zip(self.log_mel.iter())
+                .map(|(b, l)| b * l)
+                .sum();
+            *coefficient = value * self.lifter[k];
+        }
+    }
+}
+
+/// Regression deltas of `frames` over `width` frames on each side.
+///
+/// Frames past the edges repeat the first and last frame. Apply it twice for delta-deltas.
+///
+/// # Panics
+///
+/// If `width` is 0.
+pub fn deltas(frames: &[Vec<f32>], width: usize) -> Vec<Vec<f32>> {
+    assert_width(width);
+    let last = frames.len().saturating_sub(1);
+    let denominator = delta_denominator(width);
+    (0..frames.len())
+        .map(|t| {
+            let len = frames[t].len();
+            let mut delta = vec![0.0; len];
+            for n in 1..=width {
+                let next = &frames[(t + n).min(last)];
+                let previous = &frames[t.saturating_sub(n)];
+                for (i, d) in delta.iter_mut().enumerate() {
+                    *d += n as f32 * (next[i] - previous[i]);
+                }
+            }
+            delta.iter_mut().for_each(|d| *d /= denominator);
+            delta
+        })
+        .collect()
+}
+
+fn assert_width(width: usize) {
+    if width == 0 {
+        panic!("delta width must be at least one frame")
+    }
+}
+
+fn delta_denominator(width: usize) -> f32 {
+    2.0 * (1..=width).map(|n| (n * n) as f32).sum::<f32>()
+}
+
+/// Streaming version of [`deltas`].
+///
+/// The delta of a frame needs `width` future frames, so each pushed frame
+/// returns the delta of the frame pushed `width` frames earlier.
+pub struct DeltaStream {
+    width: usize,
+    history: VecDeque<Vec<f32>>,
+    delta: Vec<f32>,
+}
+
+impl DeltaStream {
+    /// # Panics
+    ///
+    /// If `width` is 0.
+    pub fn new(width: usize, len: usize) -> Self {
+        assert_width(width);
+        DeltaStream {
+            width,
+            history: VecDeque::with_capacity(2 * width + 1),
+            delta: vec![0.0; len],
+        }
+    }
+
+    /// Latency, in frames, between a pushed frame and its delta.
+    pub fn latency(&self) -> usize {
+        self.width
+    }
+
+    /// Pushes a frame and returns the delta of the frame `width` frames earlier,
+    /// or `None` until enough frames have been pushed.
+    pub fn push(&mut self, frame: &[f32]) -> Option<&[f32]> {
+        if self.history.len() == 2 * self.width + 1 {
+            let mut oldest = self.history.pop_front().expect("history is full");
+            oldest.copy_from_slice(frame);
+            self.history.push_back(oldest);
+        } else {
+            self.history.push_back(frame.to_vec());
+        }
+        if self.history.len() < 2 * self.width + 1 {
+            return None;
+        }
+        self.delta.iter_mut().for_each(|d| *d = 0.0);
+        for n in 1..=self.width {
+            let next = &self.history[self.width + n];
+            let previous = &self.history[self.width - n];
+            for (i, d) in self.delta.iter_mut().enumerate() {
+                *d += n as f32 * (next[i] - previous[i]);
+            }
+        }
+        let denominator = delta_denominator(self.width);
+        self.delta.iter_mut().for_each(|d| *d /= denominator);
+        Some(&self.delta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        filterbank::{Filterbank, MelScale},
+        test_utils::assert_all_close,
+        FrequencySpectrum, Normalization, Scale,
+    };
+
+    #[test]
+    fn matches_reference_dct_of_known_log_energies() {
+        // log energies [0, 1, 2, 3], reference from scipy.fft.dct(x, type=2, norm="ortho")
+        let mel: Vec<f32> = (0..4).map(|m| (m as f32).exp()).collect();
+        let mut mfcc = Mfcc::new(4, 4);
+        let mut out = [0.0; 4];
+        mfcc.compute(&mel, &mut out);
+        assert_all_close(&out, &[3.0, -2.230_442_5, 0.0, -0.158_512_67], 1e-4);
+    }
+
+    #[test]
+    fn matches_reference_mfcc_of_a_harmonic_signal() {
+        // Harmonics of 125 Hz with amplitude 1/k and phase k radians, reference computed in
+        // f64 with a direct DFT, HTK mel filters, natural log and an orthonormal DCT-II
+        let sample_rate = 16000.0;
+        let samples: Vec<f32> = (0..512)
+            .map(|n| {
+                (1..64)
+                    .map(|k| {
+                        let k = k as f32;
+                        (2.0 * PI * 125.0 * k * n as f32 / sample_rate + k).sin() / k
+                    })
+                    .sum()
+            })
+            .collect();
+        let mut fs = FrequencySpectrum::new(512, 1)
+            .with_sample_rate(sample_rate)
+            .with_scale(Scale::Power)
+            .with_normalization(Normalization::None);
+        let filterbank =
+            Filterbank::mel(MelScale::Htk, sample_rate, fs.fft_size(), 26, 0.0, 8000.0);
+        let mut mel = [0.0; 26];
+        filterbank.apply(fs.frequency_spectrum(&samples), &mut mel);
+        let mut mfcc = Mfcc::new(26, 13);
+        let mut out = [0.0; 13];
+        mfcc.compute(&mel, &mut out);
+        let expected = [
+            29.6728, 7.8299, 1.8909, 1.5132, 0.6096, 0.5057, 0.1654, 0.1433, -0.0139, -0.0223,
+            -0.1203, -0.1417, -0.2350,
+        ];
+        assert_all_close(&out, &expected, 1e-3);
+    }
+
+    #[test]
+    fn flat_mel_spectrum_only_has_first_coefficient() {
+        let mel = vec![std::f32::consts::E; 26];
+        let mut mfcc = Mfcc::new(26, 13);
+        let mut out = [0.0; 13];
+        mfcc.compute(&mel, &mut out);
+        let mut expected = [0.0; 13];
+        expected[0] = 26f32.sqrt();
+        assert_all_close(&out, &expected, 1e-4);
+    }
+
+    #[test]
+    fn cosine_log_spectrum_maps_to_a_single_coefficient() {
+        let num_mels = 40;
+        let mel: Vec<f32> = (0..num_mels)
+            .map(|m| (PI * 3.0 * (m as f32 + 0.5) / num_mels as f32).cos().exp())
+            .collect();
+        let mut mfcc = Mfcc::new(num_mels, 13);
+        let mut out = [0.0; 13];
+        mfcc.compute(&mel, &mut out);
+        let mut expected = [0.0; 13];
+        expected[3] = (num_mels as f32 / 2.0).sqrt();
+        assert_all_close(&out, &expected, 1e-4);
+    }
+
+    #[test]
+    fn lifter_scales_coefficients() {
+        let mel: Vec<f32> = (0..4).map(|m| (m as f32).exp()).collect();
+        let mut mfcc = Mfcc::new(4, 4).with_lifter(22.0);
+        let mut out = [0.0; 4];
+        mfcc.compute(&mel, &mut out);
+        let factor = 1.0 + 11.0 * (PI / 22.0).sin();
+        assert!((out[0] - 3.0).abs() < 1e-4);
+        assert!((out[1] + 2.230_442_5 * factor).abs() < 1e-4);
+    }
+
+    #[test]
+    fn deltas_of_a_ramp_are_constant() {
+        let frames: Vec<Vec<f32>> = (0..10).map(|t| vec![t as f32, 2.0 * t as f32]).collect();
+        let delta = deltas(&frames, 2);
+        for d in &delta[2..8] {
+            assert_all_close(d, &[1.0, 2.0], 1e-5);
+        }
+        let delta_delta = deltas(&delta, 2);
+        for d in &delta_delta[4..6] {
+            assert_all_close(d, &[0.0, 0.0], 1e-5);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn deltas_need_a_width() {
+        deltas(&[vec![1.0]], 0);
+    }
+
+    #[test]
+    fn delta_stream_matches_batch_deltas() {
+        let frames: Vec<Vec<f32>> = (0..12).map(|t| vec![(t as f32).sin(), t as f32]).collect();
+        let batch = deltas(&frames, 2);
+        let mut stream = DeltaStream::new(2, 2);
+        let mut streamed = Vec::new();
+        for frame in &frames {
+            if let Some(delta) = stream.push(frame) {
+                streamed.push(delta.to_vec());
+            }
+        }
+        assert_eq!(streamed.len(), frames.len() - 2 * stream.latency());
+        for (s, b) in streamed.iter().zip(batch[2..].iter()) {
+            assert_all_close(s, b, 1e-5);
+        }
+    }
+}
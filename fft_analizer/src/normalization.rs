@@ -0,0 +1,150 @@
+use std::fmt;
+
+use crate::{FrequencySpectrum, Scale};
+
+/// How each computed spectrum is rescaled before being returned.
+///
+/// Strategies other than [`Normalization::None`] and [`Normalization::MinMax`] divide by a
+/// reference level, so they need the non-negative [`Scale::Linear`] or [`Scale::Power`]
+/// scales and are rejected with [`Scale::Dbfs`].
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Normalization {
+    /// Values are returned in the configured [`crate::Scale`].
+    None,
+    /// Each frame is rescaled between 0 and 1 using its own min and max.
+    #[default]
+    MinMax,
+    /// Values are divided by a fixed `reference` level.
+    Fixed { reference: f32 },
+    /// Values are divided by a running peak that is multiplied by `decay` (in `0..=1`) every frame.
+    RunningPeak { decay: f32 },
+    /// Values are divided by a level that follows the maximum of each frame.
+    ///
+    /// `attack` and `release` are the time constants in seconds used when the maximum
+    /// rises above or falls below the current level, see [`FrequencySpectrum::with_frame_rate`].
+    Agc { attack: f32, release: f32 },
+}
+
+impl Normalization {
+    /// Checks the parameters and that the strategy can be used on values in `scale`.
+    ///
+    /// `reference` must be finite and positive, `decay` in `0..=1` and the time constants
+    /// finite and not negative.
+    pub fn validate(&self, scale: Scale) -> Result<(), NormalizationError> {
+        let valid = match *self {
+            Normalization::None | Normalization::MinMax => return Ok(()),
+            Normalization::Fixed { reference } => reference.is_finite() && reference > 0.0,
+            Normalization::RunningPeak { decay } => (0.0..=1.0).contains(&decay),
+            Normalization::Agc { attack, release } => [attack, release]
+                .iter()
+                .all(|time| time.is_finite() && *time >= 0.0),
+        };
+        if !valid {
+            return Err(NormalizationError::Parameter(*self));
+        }
+        if let Scale::Dbfs { .. } = scale {
+            return Err(NormalizationError::Scale(*self, scale));
+        }
+        Ok(())
+    }
+}
+
+/// Invalid [`Normalization`] rejected by [`Normalization::validate`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NormalizationError {
+    /// A parameter is out of range
+    Parameter(Normalization),
+    /// The strategy divides by a level, which doesn't work on decibels
+    Scale(Normalization, Scale),
+}
+
+impl fmt::Display for NormalizationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NormalizationError::Parameter(normalization) => write!(
+                f,
+                "invalid normalization {:?}: reference must be finite and positive, \
+                 decay in 0..=1 and time constants finite and not negative",
+                normalization
+            ),
+            NormalizationError::Scale(normalization, scale) => write!(
+                f,
+                "normalization {:?} divides by a level and can't be used with the {:?} scale",
+                normalization, scale
+            ),
+        }
+    }
+}
+
+impl std::error::Error for NormalizationError {}
+
+/// A [`Normalization`] together with the state it needs between frames.
+///
+/// [`crate::FrequencySpectrum`] keeps one per output, use it directly to normalize other
+/// frames such as filter bank levels.
+#[derive(Debug, Clone)]
+pub struct Normalizer {
+    normalization: Normalization,
+    level: f32,
+}
+
+impl Normalizer {
+    /// # Panics
+    ///
+    /// If a parameter of `normalization` is out of range, see [`Normalization::validate`].
+    pub fn new(normalization: Normalization) -> Self {
+        if let Err(error @ NormalizationError::Parameter(_)) = normalization.validate(Scale::Linear)
+        {
+            panic!("{}", error)
+        }
+        Normalizer {
+            normalization,
+            level: 0.0,
+        }
+    }
+
+    pub fn normalization(&self) -> Normalization {
+        self.normalization
+    }
+
+    /// Normalizes a frame, `frame_rate` frames being computed per second.
+    pub fn apply(&mut self, input: &mut [f32], frame_rate: f32) {
+        match self.normalization {
+            Normalization::None => {}
+            Normalization::MinMax => FrequencySpectrum::normalize(input),
+            Normalization::Fixed { reference } => divide(input, reference),
+            Normalization::RunningPeak { decay } => {
+                self.level = (self.level * decay).max(max(input));
+                divide(input, self.level);
+            }
+            Normalization::Agc { attack, release } => {
+                let target = max(input);
+                let time = if target > self.level { attack } else { release };
+                self.level += (target - self.level) * coefficient(time * frame_rate);
+                divide(input, self.level);
+            }
+        }
+    }
+}
+
+/// One-pole smoothing coefficient for a time constant of `frames`.
+fn coefficient(frames: f32) -> f32 {
+    if frames <= 0.0 {
+        1.0
+    } else {
+        1.0 - (-1.0 / frames).exp()
+    }
+}
+
+fn max(input: &[f32]) -> f32 {
+    input.iter().cloned().fold(0.0, f32::max)
+}
+
+/// Divides by `level`, silence is kept at 0 instead of dividing by 0.
+fn divide(input: &mut [f32], level: f32) {
+    if level <= 0.0 {
+        input.iter_mut().for_each(|value| *value = 0.0);
+    } else {
+        input.iter_mut().for_each(|value| *value /= level);
+    }
+}
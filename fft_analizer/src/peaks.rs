@@ -0,0 +1,276 @@
+use crate::FrequencyAxis;
+
+/// Sub-bin refinement of peak positions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Interpolation {
+    /// Peaks are reported at the centre of their bin.
+    None,
+    /// Parabola through the peak bin and its neighbours.
+    Parabolic,
+    /// Parabola through the log magnitudes, exact for Gaussian shaped peaks
+    /// and close to it for the usual windows.
+    #[default]
+    Gaussian,
+}
+
+/// A spectral peak.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Peak {
+    /// Interpolated frequency in Hz
+    pub frequency: f32,
+    /// Interpolated magnitude
+    pub amplitude: f32,
+    /// Interpolated spectrum index
+    pub position: f32,
+    /// Height of the peak above the highest of its two surrounding minima
+    pub prominence: f32,
+}
+
+/// Finds peaks in the output of [`crate::FrequencySpectrum::frequency_spectrum`].
+///
+/// Expects linear magnitudes (or power). Peaks are returned loudest first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PeakFinder {
+    min_prominence: f32,
+    min_distance: usize,
+    max_peaks: usize,
+    interpolation: Interpolation,
+}
+
+impl Default for PeakFinder {
+    fn default() -> Self {
+        PeakFinder::new()
+    }
+}
+
+impl PeakFinder {
+    /// Finds every local maximum, refined with [`Interpolation::Gaussian`].
+    pub fn new() -> Self {
+        PeakFinder {
+            min_prominence: 0.0,
+            min_distance: 1,
+            max_peaks: usize::MAX,
+            interpolation: Interpolation::default(),
+        }
+    }
+
+    /// Discards peaks less prominent than `min_prominence`.
+    pub fn with_min_prominence(mut self, min_prominence: f32) -> Self {
+        self.min_prominence = min_prominence;
+        self
+    }
+
+    /// Discards peaks closer than `min_distance` bins to a louder peak.
+    pub fn with_min_distance(mut self, min_distance: usize) -> Self {
+        self.min_distance = min_distance.max(1);
+        self
+    }
+
+    /// Keeps only the `max_peaks` loudest peaks.
+    pub fn with_max_peaks(mut self, max_peaks: usize) -> Self {
+        self.max_peaks = max_peaks;
+        self
+    }
+
+    pub fn with_interpolation(mut self, interpolation: Interpolation) -> Self {
+        self.interpolation = interpolation;
+        self
+    }
+
+    /// Finds the peaks of `spectrum`, `axis` maps their positions to Hz.
+    pub fn find(&self, spectrum: &[f32], axis: FrequencyAxis) -> Vec<Peak> {
+        let mut peaks = Vec::new();
+        self.find_into(spectrum, axis, &mut peaks);
+        peaks
+    }
+
+    /// Same as [`PeakFinder::find`] but reuses the allocation of `peaks`.
+    pub fn find_into(&self, spectrum: &[f32], axis: FrequencyAxis, peaks: &mut Vec<Peak>) {
+        peaks.clear();
+        if spectrum.len() < 3 {
+            return;
+        }
+        for i in 1..spectrum.len() - 1 {
+            // `>=` on the right keeps the first bin of a plateau
+            if spectrum[i] > spectrum[i - 1] && spectrum[i] >= spectrum[i + 1] {
+                let prominence = prominence(spectrum, i);
+                if prominence >= self.min_prominence {
+                    peaks.push(Peak {
+                        frequency: 0.0,
+                        amplitude: spectrum[i],
+                        position: i as f32,
+                        prominence,
+                    });
+                }
+            }
+        }
+
+        peaks.sort_by(|a, b| b.amplitude.total_cmp(&a.amplitude));
+        let mut kept = 0;
+        for i in 0..peaks.len() {
+            if kept == self.max_peaks {
+                break;
+            }
+            let candidate = peaks[i];
+            let too_close = peaks[..kept]
+                .iter()
+                .any(|p| (p.position - candidate.position).abs() < self.min_distance as f32);
+            if !too_close {
+                peaks[kept] = candidate;
+                kept += 1;
+            }
+        }
+        peaks.truncate(kept);
+
+        for peak in peaks.iter_mut() {
+            let i = peak.position as usize;
+            let (offset, amplitude) =
+                self.interpolate(spectrum[i - 1], spectrum[i], spectrum[i + 1]);
+            peak.position = i as f32 + offset;
+            peak.amplitude = amplitude;
+            peak.frequency = (peak.position + 1.0) * axis.bin_width();
+        }
+    }
+
+    /// Offset from the centre bin, in `-0.5..=0.5`, and amplitude of the interpolated peak.
+    fn interpolate(&self, left: f32, center: f32, right: f32) -> (f32, f32) {
+        match self.interpolation {
+            Interpolation::None => (0.0, center),
+            Interpolation::Parabolic => parabola(left, center, right),
+            Interpolation::Gaussian => {
+                if left <= 0.0 || right <= 0.0 {
+                    return (0.0, center);
+                }
+                let (offset, log) = parabola(left.ln(), center.ln(), right.ln());
+                (offset, log.exp())
+            }
+        }
+    }
+}
+
+/// Vertex of the parabola through `(-1, a)`, `(0, b)` and `(1, c)`.
+fn parabola(a: f32, b: f32, c: f32) -> (f32, f32) {
+    let denominator = a - 2.0 * b + c;
+    if denominator == 0.0 {
+        return (0.0, b);
+    }
+    let offset = (0.5 * (a - c) / denominator).clamp(-0.5, 0.5);
+    (offset, b - 0.25 * (a - c) * offset)
+}
+
+/// Height of the peak at `index` above the higher of the minima that separate it
+/// from a higher point (or the edge) on each side.
+fn prominence(spectrum: &[f32], index: usize) -> f32 {
+    let height = spectrum[index];
+    let base = |range: &mut dyn Iterator<Item = usize>| {
+        let mut min = height;
+        for j in range {
+            if spectrum[j] > height {
+                break;
+            }
+            min = min.min(spectrum[j]);
+        }
+        min
+    };
+    let left = base(&mut (0..index).rev());
+    let right = base(&mut (index + 1..spectrum.len()));
+    height - left.max(right)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_utils::{sine, tones},
+        FrequencySpectrum, Normalization,
+    };
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    const LEN: usize = 4096;
+
+    fn analyse(samples: &[f32]) -> (Vec<f32>, FrequencyAxis, f32) {
+        let mut fs = FrequencySpectrum::new(samples.len(), 1)
+            .with_sample_rate(SAMPLE_RATE)
+            .with_normalization(Normalization::None);
+        let spectrum = fs.frequency_spectrum(samples).to_vec();
+        let full_scale = LEN as f32 * fs.window().coherent_gain() / 2.0;
+        (spectrum, fs.axis(), full_scale)
+    }
+
+    #[test]
+    fn gaussian_interpolation_refines_an_off_bin_tone() {
+        let bin_width = SAMPLE_RATE / LEN as f32;
+        let hz = 100.3 * bin_width;
+        let (spectrum, axis, full_scale) = analyse(&sine(hz, SAMPLE_RATE, LEN));
+        let peaks = PeakFinder::new().with_max_peaks(1).find(&spectrum, axis);
+        assert_eq!(peaks.len(), 1);
+        assert!(
+            (peaks[0].frequency - hz).abs() < 0.02 * bin_width,
+            "{}",
+            peaks[0].frequency
+        );
+        assert!(
+            (peaks[0].amplitude / full_scale - 1.0).abs() < 0.02,
+            "{} {}",
+            peaks[0].amplitude,
+            full_scale
+        );
+
+        let rough = PeakFinder::new()
+            .with_max_peaks(1)
+            .with_interpolation(Interpolation::None)
+            .find(&spectrum, axis);
+        assert_eq!(rough[0].frequency, 100.0 * bin_width);
+    }
+
+    #[test]
+    fn peaks_are_sorted_loudest_first_and_limited() {
+        let (spectrum, axis, _) = analyse(&tones(
+            &[(440.0, 0.25), (1000.0, 1.0), (3000.0, 0.5)],
+            SAMPLE_RATE,
+            LEN,
+        ));
+        let finder = PeakFinder::new().with_min_prominence(10.0);
+        let peaks = finder.find(&spectrum, axis);
+        assert_eq!(peaks.len(), 3);
+        for (peak, hz) in peaks.iter().zip([1000.0, 3000.0, 440.0]) {
+            assert!(
+                (peak.frequency - hz).abs() < 1.0,
+                "{} != {}",
+                peak.frequency,
+                hz
+            );
+        }
+
+        let peaks = finder.with_max_peaks(2).find(&spectrum, axis);
+        assert_eq!(peaks.len(), 2);
+    }
+
+    #[test]
+    fn prominence_discards_small_bumps() {
+        let spectrum = [0.0, 1.0, 0.5, 0.6, 0.2, 3.0, 0.0];
+        let axis = FrequencyAxis::new(14.0, 14);
+        let all = PeakFinder::new().find(&spectrum, axis);
+        assert_eq!(all.len(), 3);
+        let prominent = PeakFinder::new()
+            .with_min_prominence(0.5)
+            .with_interpolation(Interpolation::None)
+            .find(&spectrum, axis);
+        let positions: Vec<f32> = prominent.iter().map(|p| p.position).collect();
+        assert_eq!(positions, vec![5.0, 1.0]);
+        assert_eq!(prominent[0].prominence, 3.0);
+        assert!((prominent[1].prominence - 0.8).abs() < 1e-6);
+    }
+
+    #[test]
+    fn min_distance_keeps_the_louder_of_close_peaks() {
+        let spectrum = [0.0, 2.0, 1.0, 3.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+        let axis = FrequencyAxis::new(18.0, 18);
+        let peaks = PeakFinder::new()
+            .with_min_distance(3)
+            .with_interpolation(Interpolation::None)
+            .find(&spectrum, axis);
+        let positions: Vec<f32> = peaks.iter().map(|p| p.position).collect();
+        assert_eq!(positions, vec![3.0, 7.0]);
+    }
+}
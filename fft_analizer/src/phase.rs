@@ -0,0 +1,49 @@
+use std::f32::consts::PI;
+
+/// Wraps `phase` into `(-PI, PI]`.
+pub fn principal_argument(phase: f32) -> f32 {
+    let wrapped = (phase + PI).rem_euclid(2.0 * PI) - PI;
+    if wrapped == -PI {
+        PI
+    } else {
+        wrapped
+    }
+}
+
+/// Unwraps consecutive phases in place so that no step is larger than `PI`.
+pub fn unwrap(phase: &mut [f32]) {
+    let mut offset = 0.0;
+    let mut previous = match phase.first() {
+        Some(&first) => first,
+        None => return,
+    };
+    for value in phase.iter_mut().skip(1) {
+        let current = *value;
+        let step = current - previous;
+        offset -= step - principal_argument(step);
+        previous = current;
+        *value = current + offset;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn principal_argument_wraps_into_range() {
+        assert!((principal_argument(3.0 * PI) - PI).abs() < 1e-5);
+        assert!((principal_argument(-3.0 * PI / 2.0) - PI / 2.0).abs() < 1e-5);
+        assert!((principal_argument(0.5) - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn unwrap_restores_a_linear_ramp() {
+        let ramp: Vec<f32> = (0..50).map(|i| -0.9 * i as f32).collect();
+        let mut wrapped: Vec<f32> = ramp.iter().map(|&p| principal_argument(p)).collect();
+        unwrap(&mut wrapped);
+        for (a, b) in wrapped.iter().zip(ramp.iter()) {
+            assert!((a - b).abs() < 1e-3, "{} != {}", a, b);
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use rustfft::num_complex::Complex;
+
+/// Scale of the magnitudes returned by [`crate::FrequencySpectrum`].
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Scale {
+    /// Magnitude of each bin, `|X|`.
+    #[default]
+    Linear,
+    /// Power of each bin, `|X|²`.
+    Power,
+    /// Decibels relative to full scale, a full-scale sine reads 0 dB.
+    /// Values below `floor` (e.g. -120.0) are clamped to it.
+    Dbfs { floor: f32 },
+}
+
+impl Scale {
+    /// Converts a bin into this scale.
+    ///
+    /// `full_scale` is the bin magnitude a full-scale sine produces,
+    /// i.e. `fft_len * coherent_gain / 2`.
+    pub(crate) fn apply(&self, bin: Complex<f32>, full_scale: f32) -> f32 {
+        match *self {
+            Scale::Power => bin.norm_sqr(),
+            _ => self.convert(bin.norm(), full_scale),
+        }
+    }
+
+    /// Converts a linear magnitude, e.g. the level of a filter band, into this scale.
+    ///
+    /// `full_scale` is the magnitude a full-scale sine produces.
+    pub fn convert(&self, magnitude: f32, full_scale: f32) -> f32 {
+        match *self {
+            Scale::Linear => magnitude,
+            Scale::Power => magnitude * magnitude,
+            Scale::Dbfs { floor } => {
+                let amplitude = magnitude / full_scale;
+                if amplitude > 0.0 {
+                    (20.0 * amplitude.log10()).max(floor)
+                } else {
+                    floor
+                }
+            }
+        }
+    }
+}
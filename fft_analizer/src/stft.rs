@@ -0,0 +1,263 @@
+use std::sync::Arc;
+
+use realfft::{ComplexToReal, RealFftPlanner, RealToComplex};
+use rustfft::num_complex::Complex;
+
+use crate::{
+    window::{Window, WindowFunction},
+    Framer,
+};
+
+/// Overlap sums below this value can't be normalized.
+const MIN_OVERLAP_SUM: f32 = 1e-6;
+
+/// Streaming short-time Fourier transform of a mono signal.
+///
+/// Unlike [`crate::FrequencySpectrum`], frames hold every non-negative bin, DC included,
+/// so they can be resynthesized with [`Istft`].
+pub struct Stft {
+    window: Window,
+    fft: Arc<dyn RealToComplex<f32>>,
+    framer: Framer,
+    /// Windowed copy of the last frame, transformed in place
+    frame: Vec<f32>,
+    bins: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+}
+
+impl Stft {
+    /// # Panics
+    ///
+    /// If `hop` is 0 or greater than `frame_len`.
+    pub fn new(function: WindowFunction, frame_len: usize, hop: usize) -> Self {
+        let framer = Framer::new(frame_len, hop);
+        let fft = RealFftPlanner::<f32>::new().plan_fft_forward(frame_len);
+        Stft {
+            window: Window::new(function, frame_len),
+            bins: fft.make_output_vec(),
+            scratch: fft.make_scratch_vec(),
+            fft,
+            framer,
+            frame: vec![0.0; frame_len],
+        }
+    }
+
+    pub fn frame_len(&self) -> usize {
+        self.framer.frame_len()
+    }
+
+    pub fn hop(&self) -> usize {
+        self.framer.hop()
+    }
+
+    /// Number of bins per frame, `frame_len / 2 + 1`.
+    pub fn bins_len(&self) -> usize {
+        self.bins.len()
+    }
+
+    /// Appends `samples` and calls `on_frame` with the bins of every frame completed by them.
+    ///
+    /// The first frame is computed once `frame_len` samples arrived, then one every `hop` samples.
+    pub fn push(&mut self, samples: &[f32], mut on_frame: impl FnMut(&[Complex<f32>])) {
+        for &sample in samples {
+            if self.framer.push(sample) {
+                self.frame.copy_from_slice(self.framer.frame());
+                self.window.apply(&mut self.frame);
+                self.fft
+                    .process_with_scratch(&mut self.frame, &mut self.bins, &mut self.scratch)
+                    .expect("buffers are sized by the planner");
+                on_frame(&self.bins);
+            }
+        }
+    }
+}
+
+/// Inverse short-time Fourier transform with weighted overlap-add.
+///
+/// Each frame is inverse transformed, multiplied by the synthesis window and added to the
+/// previous ones. The output is divided by the overlap sum of the analysis and synthesis
+/// windows, so an unmodified [`Stft`] with the same window and hop reconstructs its input.
+/// The first `frame_len - hop` samples lack earlier frames and fade in.
+pub struct Istft {
+    window: Window,
+    hop: usize,
+    fft: Arc<dyn ComplexToReal<f32>>,
+    bins: Vec<Complex<f32>>,
+    frame: Vec<f32>,
+    scratch: Vec<Complex<f32>>,
+    /// Overlap-add of the frames pushed so far
+    accumulator: Vec<f32>,
+    /// Overlap sum of the squared window for each of the `hop` output positions
+    overlap_sum: Vec<f32>,
+    output: Vec<f32>,
+}
+
+impl Istft {
+    /// # Panics
+    ///
+    /// If `hop` is 0 or greater than `frame_len`, or the window doesn't overlap enough
+    /// at this hop to be normalized.
+    pub fn new(function: WindowFunction, frame_len: usize, hop: usize) -> Self {
+        if hop == 0 || hop > frame_len {
+            panic!("hop ({}) must be in 1..={}", hop, frame_len)
+        }
+        let window = Window::new(function, frame_len);
+        let mut overlap_sum = vec![0.0; hop];
+        for (n, w) in window.coefficients().iter().enumerate() {
+            overlap_sum[n % hop] += w * w;
+        }
+        if overlap_sum.iter().any(|&sum| sum < MIN_OVERLAP_SUM) {
+            panic!(
+                "window {:?} does not overlap enough with hop {} to be normalized",
+                function, hop
+            )
+        }
+        let fft = RealFftPlanner::<f32>::new().plan_fft_inverse(frame_len);
+        Istft {
+            window,
+            hop,
+            bins: fft.make_input_vec(),
+            frame: fft.make_output_vec(),
+            scratch: fft.make_scratch_vec(),
+            fft,
+            accumulator: vec![0.0; frame_len],
+            overlap_sum,
+            output: vec![0.0; hop],
+        }
+    }
+
+    pub fn frame_len(&self) -> usize {
+        self.frame.len()
+    }
+
+    pub fn hop(&self) -> usize {
+        self.hop
+    }
+
+    /// Adds a frame of `frame_len / 2 + 1` bins and returns the next `hop` output samples.
+    ///
+    /// The imaginary parts of the DC and Nyquist bins are ignored. The slice is overwritten
+    /// by the next call.
+    ///
+    /// # Panics
+    ///
+    /// If `bins` length is different than `frame_len / 2 + 1`.
+    pub fn push_frame(&mut self, bins: &[Complex<f32>]) -> &[f32] {
+        if bins.len() != self.bins.len() {
+            panic!(
+                "bins len ({}) is different than {}",
+                bins.len(),
+                self.bins.len()
+            )
+        }
+        self.bins.copy_from_slice(bins);
+        self.bins[0].im = 0.0;
+        if self.frame.len().is_multiple_of(2) {
+            let last = self.bins.len() - 1;
+            self.bins[last].im = 0.0;
+        }
+        self.fft
+            .process_with_scratch(&mut self.bins, &mut self.frame, &mut self.scratch)
+            .expect("buffers are sized by the planner");
+
+        let scale = 1.0 / self.frame.len() as f32;
+        for ((acc, sample), w) in self
+            .accumulator
+            .iter_mut()
+            .zip(self.frame.iter())
+            .zip(self.window.coefficients())
+        {
+            *acc += sample * scale * w;
+        }
+
+        for ((out, acc), sum) in self
+            .output
+            .iter_mut()
+            .zip(self.accumulator.iter())
+            .zip(self.overlap_sum.iter())
+        {
+            *out = acc / sum;
+        }
+        self.accumulator.rotate_left(self.hop);
+        let len = self.accumulator.len();
+        self.accumulator[len - self.hop..].fill(0.0);
+        &self.output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::noise;
+
+    fn round_trip(
+        function: WindowFunction,
+        frame_len: usize,
+        hop: usize,
+        signal: &[f32],
+    ) -> Vec<f32> {
+        let mut stft = Stft::new(function, frame_len, hop);
+        let mut istft = Istft::new(function, frame_len, hop);
+        let mut output = Vec::new();
+        for chunk in signal.chunks(100) {
+            stft.push(chunk, |bins| {
+                output.extend_from_slice(istft.push_frame(bins));
+            });
+        }
+        output
+    }
+
+    fn assert_reconstructs(frame_len: usize, hop: usize, signal: &[f32], output: &[f32]) {
+        // Output sample t is input sample t once every frame covering it has been added
+        let start = frame_len - hop;
+        assert!(output.len() > start + frame_len);
+        for t in start..output.len() {
+            assert!(
+                (output[t] - signal[t]).abs() < 1e-4,
+                "sample {}: {} != {}",
+                t,
+                output[t],
+                signal[t]
+            );
+        }
+    }
+
+    #[test]
+    fn hann_with_75_percent_overlap_reconstructs_the_signal() {
+        let signal = noise(8192, 7);
+        let output = round_trip(WindowFunction::Hann, 512, 128, &signal);
+        assert_reconstructs(512, 128, &signal, &output);
+    }
+
+    #[test]
+    fn other_windows_and_hops_reconstruct_the_signal() {
+        let signal = noise(4096, 7);
+        for (function, hop) in [
+            (WindowFunction::Hann, 256),
+            (WindowFunction::Hamming, 256),
+            (WindowFunction::BlackmanHarris, 64),
+            (WindowFunction::Tukey { alpha: 0.0 }, 512),
+        ] {
+            let output = round_trip(function, 512, hop, &signal);
+            assert_reconstructs(512, hop, &signal, &output);
+        }
+    }
+
+    #[test]
+    fn frames_hold_dc_and_nyquist() {
+        let mut stft = Stft::new(WindowFunction::Tukey { alpha: 0.0 }, 8, 8);
+        let mut frames = 0;
+        stft.push(&[1.0; 8], |bins| {
+            assert_eq!(bins.len(), 5);
+            assert!((bins[0].re - 8.0).abs() < 1e-5);
+            frames += 1;
+        });
+        assert_eq!(frames, 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn hann_without_overlap_cannot_be_normalized() {
+        let _ = Istft::new(WindowFunction::Hann, 512, 512);
+    }
+}
@@ -0,0 +1,40 @@
+use std::f32::consts::PI;
+
+/// Deterministic white noise in `-1.0..1.0`, so spectra have energy in every bin.
+pub(crate) fn noise(len: usize, seed: u32) -> Vec<f32> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            (state >> 16) as f32 / 32768.0 - 1.0
+        })
+        .collect()
+}
+
+/// Sum of `(hz, amplitude)` sines sampled at `sample_rate`.
+pub(crate) fn tones(tones: &[(f32, f32)], sample_rate: f32, len: usize) -> Vec<f32> {
+    (0..len)
+        .map(|n| {
+            tones
+                .iter()
+                .map(|(hz, amplitude)| amplitude * (2.0 * PI * hz * n as f32 / sample_rate).sin())
+                .sum()
+        })
+        .collect()
+}
+
+/// Full-scale sine at `hz` sampled at `sample_rate`.
+pub(crate) fn sine(hz: f32, sample_rate: f32, len: usize) -> Vec<f32> {
+    tones(&[(hz, 1.0)], sample_rate, len)
+}
+
+pub(crate) fn assert_close(a: f32, b: f32, tolerance: f32) {
+    assert!((a - b).abs() < tolerance, "{} is not close to {}", a, b);
+}
+
+pub(crate) fn assert_all_close(a: &[f32], b: &[f32], tolerance: f32) {
+    assert_eq!(a.len(), b.len());
+    for (x, y) in a.iter().zip(b.iter()) {
+        assert!((x - y).abs() < tolerance, "{:?} is not close to {:?}", a, b);
+    }
+}
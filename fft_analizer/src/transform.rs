@@ -0,0 +1,91 @@
+use std::sync::Arc;
+
+use realfft::{RealFftPlanner, RealToComplex};
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+
+/// Algorithm used to transform the real input samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FftMode {
+    /// Real-to-complex FFT, roughly half the work of the complex one.
+    #[default]
+    Real,
+    /// Full complex FFT over the samples with zero imaginary part.
+    Complex,
+}
+
+/// A planned forward FFT together with its working buffers.
+pub(crate) enum Transform {
+    Complex {
+        fft: Arc<dyn Fft<f32>>,
+        buffer: Vec<Complex<f32>>,
+        scratch: Vec<Complex<f32>>,
+    },
+    Real {
+        fft: Arc<dyn RealToComplex<f32>>,
+        output: Vec<Complex<f32>>,
+        scratch: Vec<Complex<f32>>,
+    },
+}
+
+impl Transform {
+    pub(crate) fn new(mode: FftMode, len: usize) -> Self {
+        match mode {
+            FftMode::Complex => {
+                let fft = FftPlanner::<f32>::new().plan_fft_forward(len);
+                let buffer = vec![Complex::new(0.0, 0.0); len];
+                let scratch = vec![Complex::new(0.0, 0.0); fft.get_inplace_scratch_len()];
+                Transform::Complex {
+                    fft,
+                    buffer,
+                    scratch,
+                }
+            }
+            FftMode::Real => {
+                let fft = RealFftPlanner::<f32>::new().plan_fft_forward(len);
+                let output = fft.make_output_vec();
+                let scratch = fft.make_scratch_vec();
+                Transform::Real {
+                    fft,
+                    output,
+                    scratch,
+                }
+            }
+        }
+    }
+
+    pub(crate) fn mode(&self) -> FftMode {
+        match self {
+            Transform::Complex { .. } => FftMode::Complex,
+            Transform::Real { .. } => FftMode::Real,
+        }
+    }
+
+    /// Transforms `samples` and returns the non-negative frequency bins, DC and Nyquist included.
+    ///
+    /// `samples` is used as scratch space and left in an unspecified state.
+    pub(crate) fn process(&mut self, samples: &mut [f32]) -> &[Complex<f32>] {
+        match self {
+            Transform::Complex {
+                fft,
+                buffer,
+                scratch,
+            } => {
+                for (value, &sample) in buffer.iter_mut().zip(samples.iter()) {
+                    *value = Complex::new(sample, 0.0);
+                }
+                fft.process_with_scratch(buffer, scratch);
+                let half = buffer.len() / 2;
+                &buffer[..half + 1]
+            }
+            Transform::Real {
+                fft,
+                output,
+                scratch,
+            } => {
+                fft.process_with_scratch(samples, output, scratch)
+                    .expect("buffers are sized by the planner");
+                output
+            }
+        }
+    }
+}
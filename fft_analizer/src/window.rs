@@ -0,0 +1,234 @@
+use std::f32::consts::PI;
+
+/// Window functions that can be applied to a frame before the FFT.
+///
+/// All windows are symmetric, i.e. the first and last coefficients are equal.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum WindowFunction {
+    #[default]
+    Hann,
+    Hamming,
+    Blackman,
+    /// 4-term Blackman-Harris, very low sidelobes for spectrograms.
+    BlackmanHarris,
+    /// Flat-top window, accurate amplitudes for level metering.
+    FlatTop,
+    /// Kaiser window, `beta` trades main lobe width against sidelobe level.
+    Kaiser {
+        beta: f32,
+    },
+    /// Tukey (tapered cosine) window, `alpha` is the tapered fraction in `0..=1`.
+    Tukey {
+        alpha: f32,
+    },
+}
+
+impl WindowFunction {
+    /// Value of the window at position `i` of a window of length `n`.
+    fn value(&self, i: usize, n: usize) -> f32 {
+        if n == 1 {
+            return 1.0;
+        }
+        let m = (n - 1) as f32;
+        let x = 2.0 * PI * i as f32 / m;
+        match *self {
+            WindowFunction::Hann => 0.5 * (1.0 - x.cos()),
+            WindowFunction::Hamming => 0.54 - 0.46 * x.cos(),
+            WindowFunction::Blackman => 0.42 - 0.5 * x.cos() + 0.08 * (2.0 * x).cos(),
+            WindowFunction::BlackmanHarris => cosine_sum(x, &[0.35875, 0.48829, 0.14128, 0.01168]),
+            WindowFunction::FlatTop => cosine_sum(
+                x,
+                &[0.21557895, 0.41663158, 0.27726316, 0.083578947, 0.006947368],
+            ),
+            WindowFunction::Kaiser { beta } => {
+                let r = 2.0 * i as f32 / m - 1.0;
+                bessel_i0(beta * (1.0 - r * r).max(0.0).sqrt()) / bessel_i0(beta)
+            }
+            WindowFunction::Tukey { alpha } => {
+                let alpha = alpha.clamp(0.0, 1.0);
+                if alpha == 0.0 {
+                    return 1.0;
+                }
+                let edge = alpha * m / 2.0;
+                let pos = i as f32;
+                if pos < edge {
+                    0.5 * (1.0 - (PI * pos / edge).cos())
+                } else if pos > m - edge {
+                    0.5 * (1.0 - (PI * (m - pos) / edge).cos())
+                } else {
+                    1.0
+                }
+            }
+        }
+    }
+}
+
+/// Generalized cosine window: `a0 - a1 cos(x) + a2 cos(2x) - a3 cos(3x) ...`
+fn cosine_sum(x: f32, coefficients: &[f32]) -> f32 {
+    coefficients
+        .iter()
+        .enumerate()
+        .map(|(k, a)| {
+            let sign = if k % 2 == 0 { 1.0 } else { -1.0 };
+            sign * a * (k as f32 * x).cos()
+        })
+        .sum()
+}
+
+/// Zeroth order modified Bessel function of the first kind, used by the Kaiser window.
+fn bessel_i0(x: f32) -> f32 {
+    let half = x / 2.0;
+    let mut term = 1.0;
+    let mut sum = 1.0;
+    let mut k = 1.0;
+    while term > sum * 1e-10 {
+        term *= (half / k) * (half / k);
+        sum += term;
+        k += 1.0;
+    }
+    sum
+}
+
+/// Precomputed window coefficients for a fixed frame length.
+pub struct Window {
+    function: WindowFunction,
+    coefficients: Vec<f32>,
+}
+
+impl Window {
+    pub fn new(function: WindowFunction, n: usize) -> Self {
+        let coefficients = (0..n).map(|i| function.value(i, n)).collect();
+        Window {
+            function,
+            coefficients,
+        }
+    }
+
+    pub fn function(&self) -> WindowFunction {
+        self.function
+    }
+
+    pub fn len(&self) -> usize {
+        self.coefficients.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.coefficients.is_empty()
+    }
+
+    pub fn coefficients(&self) -> &[f32] {
+        &self.coefficients
+    }
+
+    /// Mean value of the window.
+    ///
+    /// A sinusoid's peak bin magnitude is scaled by this factor, divide by it to correct amplitudes.
+    pub fn coherent_gain(&self) -> f32 {
+        if self.coefficients.is_empty() {
+            return 0.0;
+        }
+        self.coefficients.iter().sum::<f32>() / self.coefficients.len() as f32
+    }
+
+    /// Equivalent noise bandwidth in bins.
+    ///
+    /// Width of a rectangular filter that lets through the same noise power as the window.
+    pub fn equivalent_noise_bandwidth(&self) -> f32 {
+        let sum: f32 = self.coefficients.iter().sum();
+        if sum == 0.0 {
+            return 0.0;
+        }
+        let sum_sq: f32 = self.coefficients.iter().map(|w| w * w).sum();
+        self.coefficients.len() as f32 * sum_sq / (sum * sum)
+    }
+
+    pub fn apply(&self, samples: &mut [f32]) {
+        if samples.len() != self.coefficients.len() {
+            panic!(
+                "samples len ({}) is different than window len ({})",
+                samples.len(),
+                self.coefficients.len()
+            )
+        }
+        for (sample, w) in samples.iter_mut().zip(self.coefficients.iter()) {
+            *sample *= w;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::assert_close;
+
+    const N: usize = 4096;
+
+    #[test]
+    fn windows_are_symmetric() {
+        let functions = [
+            WindowFunction::Hann,
+            WindowFunction::Hamming,
+            WindowFunction::Blackman,
+            WindowFunction::BlackmanHarris,
+            WindowFunction::FlatTop,
+            WindowFunction::Kaiser { beta: 8.6 },
+            WindowFunction::Tukey { alpha: 0.5 },
+        ];
+        for function in functions {
+            let window = Window::new(function, 65);
+            let c = window.coefficients();
+            for i in 0..c.len() {
+                assert_close(c[i], c[c.len() - 1 - i], 1e-5);
+            }
+            assert_close(c[32], 1.0, 1e-3);
+        }
+    }
+
+    #[test]
+    fn coherent_gain_and_enbw_match_known_values() {
+        let cases = [
+            (WindowFunction::Hann, 0.5, 1.5),
+            (WindowFunction::Hamming, 0.54, 1.363),
+            (WindowFunction::Blackman, 0.42, 1.727),
+            (WindowFunction::BlackmanHarris, 0.35875, 2.004),
+            (WindowFunction::FlatTop, 0.2156, 3.77),
+        ];
+        for (function, gain, enbw) in cases {
+            let window = Window::new(function, N);
+            assert_close(window.coherent_gain(), gain, 1e-3);
+            assert_close(window.equivalent_noise_bandwidth(), enbw, 1e-2);
+        }
+    }
+
+    #[test]
+    fn tukey_limits_are_rectangular_and_hann() {
+        let rect = Window::new(WindowFunction::Tukey { alpha: 0.0 }, 64);
+        assert!(rect.coefficients().iter().all(|&w| w == 1.0));
+        assert_close(rect.equivalent_noise_bandwidth(), 1.0, 1e-6);
+
+        let tukey = Window::new(WindowFunction::Tukey { alpha: 1.0 }, 64);
+        let hann = Window::new(WindowFunction::Hann, 64);
+        for (a, b) in tukey.coefficients().iter().zip(hann.coefficients()) {
+            assert_close(*a, *b, 1e-5);
+        }
+    }
+
+    #[test]
+    fn kaiser_with_zero_beta_is_rectangular() {
+        let window = Window::new(WindowFunction::Kaiser { beta: 0.0 }, 64);
+        assert!(window
+            .coefficients()
+            .iter()
+            .all(|&w| (w - 1.0).abs() < 1e-6));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn hann_window_matches_hann() {
+        let mut a = vec![1.0; 16];
+        let mut b = vec![1.0; 16];
+        crate::hann_window::HannWindow::new(16).apply(&mut a);
+        Window::new(WindowFunction::Hann, 16).apply(&mut b);
+        assert_eq!(a, b);
+    }
+}